@@ -38,7 +38,12 @@ use tokio::sync::RwLock;
 
 // --- Internal modules ---
 use crate::config::Config;
+use crate::error::Error;
+use crate::metadata::{self, ImageMetadata};
+use crate::network::NetworkMonitor;
+use crate::retry::{self, Retry};
 use crate::service::{is_flatpak, ServiceState, WallpaperService, SERVICE_NAME, OBJECT_PATH};
+use crate::theme::ThemeMonitor;
 use crate::timer::InternalTimer;
 
 /// Application ID (must match desktop entry)
@@ -52,6 +57,8 @@ const APP_ID: &str = "io.github.reality2_roycdavies.cosmic-bing-wallpaper";
 enum ServiceCommand {
     /// Request the background service to fetch and apply today's wallpaper
     FetchWallpaper,
+    /// Request the background service to go back to the previous wallpaper
+    RevertWallpaper,
     /// Tell the background service to enable or disable the daily timer
     SetTimerEnabled(bool),
 }
@@ -62,11 +69,18 @@ enum ServiceCommand {
 #[derive(Debug)]
 enum ServiceEvent {
     /// Periodic update of timer state (sent every 500ms so UI stays in sync)
-    TimerState { enabled: bool, next_run: String },
+    /// (`deferred` explains a held-back or skipped run, e.g., "Waiting for network")
+    TimerState { enabled: bool, next_run: String, deferred: Option<String> },
     /// A wallpaper fetch has started
     FetchStarted,
-    /// A wallpaper fetch completed (Ok = success message, Err = error message)
-    FetchComplete(Result<String, String>),
+    /// A fetch attempt failed with a transient error and will be retried
+    FetchRetrying(String),
+    /// A wallpaper fetch completed (Ok = success message, Err = what went wrong)
+    FetchComplete(Result<String, Error>),
+    /// Going back to the previous wallpaper completed (same as FetchComplete)
+    RevertComplete(Result<String, Error>),
+    /// The current wallpaper changed; carries its metadata index entry (if any)
+    CurrentWallpaper(Option<ImageMetadata>),
 }
 
 /// All possible user interactions and system events in the applet.
@@ -93,9 +107,15 @@ pub enum Message {
     /// User clicked the "Fetch Today's Wallpaper" button in the popup.
     /// This sends a command to the background service thread to start fetching.
     FetchWallpaper,
+    /// User clicked the "Undo" button in the popup.
+    /// Asks the background service to go back to the previous wallpaper.
+    RevertWallpaper,
     /// User toggled the daily auto-update switch in the popup.
     /// Sends a command to enable/disable the timer in the background service.
     ToggleTimer,
+    /// User clicked "Learn More" for the current wallpaper.
+    /// Opens the image's source link in the default browser.
+    OpenLink(String),
     /// User clicked the "Settings..." button in the popup.
     /// This spawns a new process: `cosmic-bing-wallpaper --settings`
     OpenSettings,
@@ -129,10 +149,14 @@ pub struct BingWallpaperApplet {
     timer_enabled: bool,
     /// Human-readable string for the next scheduled run (e.g., "Fri Feb 07 08:00")
     next_run: String,
-    /// True while a wallpaper fetch is in progress (used to disable the fetch button)
+    /// Why the timer's last due run was held back or skipped (offline, metered)
+    timer_deferred: Option<String>,
+    /// True while a wallpaper fetch or undo is in progress (used to disable the buttons)
     is_fetching: bool,
     /// Status text shown in the popup (e.g., "Ready", "Fetching...", "Applied: ...")
     fetch_status: String,
+    /// Metadata for the current wallpaper (title, copyright, source link), if known
+    current_wallpaper: Option<ImageMetadata>,
 
     // --- Communication channels with the background service thread ---
 
@@ -205,8 +229,10 @@ impl cosmic::Application for BingWallpaperApplet {
             popup: None,
             timer_enabled,
             next_run: String::new(),
+            timer_deferred: None,
             is_fetching: false,
             fetch_status: "Ready".to_string(),
+            current_wallpaper: None,
             cmd_tx,
             event_rx,
         };
@@ -234,24 +260,32 @@ impl cosmic::Application for BingWallpaperApplet {
                 // We use a while loop to process all queued events at once.
                 while let Ok(event) = self.event_rx.try_recv() {
                     match event {
-                        ServiceEvent::TimerState { enabled, next_run } => {
+                        ServiceEvent::TimerState { enabled, next_run, deferred } => {
                             // Update our local copy of the timer state for display
                             self.timer_enabled = enabled;
                             self.next_run = next_run;
+                            self.timer_deferred = deferred;
                         }
                         ServiceEvent::FetchStarted => {
                             // Show loading state in the popup
                             self.is_fetching = true;
                             self.fetch_status = "Fetching...".to_string();
                         }
-                        ServiceEvent::FetchComplete(result) => {
+                        ServiceEvent::FetchRetrying(message) => {
+                            // Still fetching; show why it's taking longer
+                            self.fetch_status = message;
+                        }
+                        ServiceEvent::FetchComplete(result) | ServiceEvent::RevertComplete(result) => {
                             // Show the result (success or error) in the popup
                             self.is_fetching = false;
                             match result {
                                 Ok(msg) => self.fetch_status = msg,
-                                Err(e) => self.fetch_status = format!("Error: {e}"),
+                                Err(e) => self.fetch_status = e.status_message(),
                             }
                         }
+                        ServiceEvent::CurrentWallpaper(info) => {
+                            self.current_wallpaper = info;
+                        }
                     }
                 }
             }
@@ -281,6 +315,13 @@ impl cosmic::Application for BingWallpaperApplet {
                 self.fetch_status = "Fetching...".to_string();
             }
 
+            Message::RevertWallpaper => {
+                // Same instant feedback as fetching; the buttons stay disabled until it's done
+                let _ = self.cmd_tx.send(ServiceCommand::RevertWallpaper);
+                self.is_fetching = true;
+                self.fetch_status = "Restoring previous wallpaper...".to_string();
+            }
+
             Message::ToggleTimer => {
                 // Toggle the timer state and notify the background thread.
                 // We update the local state immediately for instant UI feedback.
@@ -289,6 +330,13 @@ impl cosmic::Application for BingWallpaperApplet {
                 self.timer_enabled = new_state;
             }
 
+            Message::OpenLink(url) => {
+                // xdg-open hands the URL to the default browser (works via portal in Flatpak)
+                if let Err(e) = std::process::Command::new("xdg-open").arg(&url).spawn() {
+                    eprintln!("Failed to open link: {e}");
+                }
+            }
+
             Message::OpenSettings => {
                 // Launch the settings window as a separate process.
                 // We spawn a new thread to avoid blocking the UI while the process starts.
@@ -367,7 +415,7 @@ impl cosmic::Application for BingWallpaperApplet {
 
                             // Set popup dimensions
                             let popup_width = 300u32;
-                            let popup_height = 260u32;
+                            let popup_height = 340u32;
 
                             // Calculate popup position relative to the panel icon
                             let mut popup_settings = state.core.applet.get_popup_settings(
@@ -434,10 +482,12 @@ impl BingWallpaperApplet {
     /// ┌──────────────────────────────┐
     /// │ Bing Wallpaper               │  ← Title
     /// │ ──────────────────────────── │  ← Divider
+    /// │ Mountain Sunrise             │  ← Current wallpaper title (if known)
+    /// │ © Photographer  [Learn More] │  ← Copyright and source link
     /// │ Daily Update: ON/OFF         │  ← Timer status
     /// │ Next: Fri Feb 07 08:00       │  ← Next run time (if enabled)
     /// │ Ready / Fetching... / Error  │  ← Fetch status
-    /// │ [Fetch Today's Wallpaper]    │  ← Fetch button
+    /// │ [Fetch Today's...] [Undo]    │  ← Fetch and undo buttons
     /// │ ──────────────────────────── │  ← Divider
     /// │ Daily Update     [toggle]    │  ← Timer on/off switch
     /// │ ──────────────────────────── │  ← Divider
@@ -461,11 +511,12 @@ impl BingWallpaperApplet {
         } else {
             "Daily Update: OFF"
         };
-        // Show next scheduled run time only when timer is active
-        let next_run_text = if self.timer_enabled && !self.next_run.is_empty() {
-            format!("Next: {}", self.next_run)
-        } else {
-            String::new()
+        // Show next scheduled run time only when timer is active,
+        // or why the due run hasn't happened (offline, metered connection)
+        let next_run_text = match &self.timer_deferred {
+            Some(reason) if self.timer_enabled => reason.clone(),
+            _ if self.timer_enabled && !self.next_run.is_empty() => format!("Next: {}", self.next_run),
+            _ => String::new(),
         };
 
         // Conditionally include the "Next: ..." line
@@ -475,6 +526,20 @@ impl BingWallpaperApplet {
             column![text::body(timer_label), text::caption(next_run_text)].spacing(2)
         };
 
+        // --- Current wallpaper details (from the metadata index) ---
+        let mut wallpaper_section = column![].spacing(2);
+        if let Some(info) = &self.current_wallpaper {
+            wallpaper_section = wallpaper_section
+                .push(text::body(info.display_title().to_string()))
+                .push(text::caption(info.display_copyright().to_string()));
+            if !info.copyright_link.is_empty() {
+                wallpaper_section = wallpaper_section.push(
+                    widget::button::link("Learn More")
+                        .on_press(Message::OpenLink(info.copyright_link.clone())),
+                );
+            }
+        }
+
         // --- Fetch status and button ---
         let fetch_text = text::caption(&self.fetch_status);
 
@@ -487,6 +552,12 @@ impl BingWallpaperApplet {
                 .on_press(Message::FetchWallpaper)
                 .into()
         };
+        // Undo goes back to whatever was shown before the last change
+        let undo_btn = widget::button::standard("Undo")
+            .on_press_maybe((!self.is_fetching).then_some(Message::RevertWallpaper));
+        let fetch_row = row![fetch_btn, undo_btn]
+            .spacing(8)
+            .align_y(Alignment::Center);
 
         // --- Timer toggle row ---
         let timer_toggle_row = row![
@@ -525,9 +596,10 @@ impl BingWallpaperApplet {
         column![
             title_row,
             divider(),
+            wallpaper_section,
             status_section,
             fetch_text,
-            fetch_btn,
+            fetch_row,
             divider(),
             timer_toggle_row,
             divider(),
@@ -544,7 +616,9 @@ impl BingWallpaperApplet {
 /// 1. Creates and starts the internal timer (for daily wallpaper fetches)
 /// 2. Registers the D-Bus service (so the settings window can communicate with us)
 /// 3. Listens for commands from the UI thread and timer events
-/// 4. Sends status updates back to the UI thread every 500ms
+/// 4. Re-applies the wallpaper when the COSMIC theme switches between light
+///    and dark (for the dark variant)
+/// 5. Sends status updates back to the UI thread every 500ms
 ///
 /// # Arguments
 /// * `cmd_rx` - Receives commands from the UI thread (fetch, toggle timer)
@@ -561,8 +635,10 @@ async fn run_background_service(
     // The timer handles daily scheduled fetches and catch-up after boot.
     // Arc (Atomic Reference Counted) allows sharing the timer between multiple async tasks.
     let timer = Arc::new(InternalTimer::new());
+    // Watch NetworkManager so runs that come due while offline wait for the network
+    let network = NetworkMonitor::system().await;
     // start() returns a channel receiver that fires when the timer triggers
-    let mut timer_rx = timer.start();
+    let mut timer_rx = timer.start(network);
 
     // --- Create shared state ---
     // ServiceState holds the config and current wallpaper info.
@@ -605,11 +681,38 @@ async fn run_background_service(
             eprintln!("Timer fired - fetching wallpaper...");
             let _ = event_tx_timer.send(ServiceEvent::FetchStarted);
 
-            let result = do_fetch_and_apply(&state_for_timer).await;
+            let result = do_fetch_and_apply(&state_for_timer, |r| {
+                let _ = event_tx_timer.send(ServiceEvent::FetchRetrying(r.message()));
+            }).await;
             let _ = event_tx_timer.send(ServiceEvent::FetchComplete(result));
         }
     });
 
+    // --- Spawn a task to follow the theme ---
+    // When the COSMIC theme switches between light and dark, the current
+    // wallpaper is applied again so the darker variant comes or goes. Both
+    // variants are cached, so nothing is downloaded or reprocessed.
+    let state_for_theme = state.clone();
+    let mut theme = ThemeMonitor::start();
+    let _theme_handle = tokio::spawn(async move {
+        while let Some(dark) = theme.changed().await {
+            if !Config::load().dark_variant.is_active() {
+                continue;
+            }
+            let Some(path) = state_for_theme.read().await.current_path.clone() else {
+                continue;
+            };
+            eprintln!("Theme switched to {}, re-applying the wallpaper", if dark { "dark" } else { "light" });
+            if let Err(e) = crate::service::reapply_cosmic_wallpaper(&path).await {
+                eprintln!("Failed to re-apply the wallpaper: {e}");
+            }
+        }
+    });
+
+    // Path of the wallpaper last reported to the UI, so details are only
+    // looked up (and sent) when it changes
+    let mut reported_path: Option<String> = None;
+
     // --- Main event loop ---
     // Runs forever, checking for UI commands and sending status updates.
     loop {
@@ -624,10 +727,21 @@ async fn run_background_service(
                     let state_clone = state.clone();
                     let event_tx_clone = event_tx.clone();
                     tokio::spawn(async move {
-                        let result = do_fetch_and_apply(&state_clone).await;
+                        let result = do_fetch_and_apply(&state_clone, |r| {
+                            let _ = event_tx_clone.send(ServiceEvent::FetchRetrying(r.message()));
+                        }).await;
                         let _ = event_tx_clone.send(ServiceEvent::FetchComplete(result));
                     });
                 }
+                ServiceCommand::RevertWallpaper => {
+                    // Runs on its own thread inside revert_cosmic_wallpaper, so just spawn and report back
+                    let state_clone = state.clone();
+                    let event_tx_clone = event_tx.clone();
+                    tokio::spawn(async move {
+                        let result = do_revert(&state_clone).await;
+                        let _ = event_tx_clone.send(ServiceEvent::RevertComplete(result));
+                    });
+                }
                 ServiceCommand::SetTimerEnabled(enabled) => {
                     // Update the timer state (persisted to disk by set_enabled)
                     timer.set_enabled(enabled);
@@ -639,19 +753,48 @@ async fn run_background_service(
         // can display the correct toggle state and next run time
         let enabled = timer.is_enabled();
         let next_run = timer.next_run_string().await;
-        let _ = event_tx.send(ServiceEvent::TimerState { enabled, next_run });
+        let deferred = timer.deferred_reason();
+        let _ = event_tx.send(ServiceEvent::TimerState { enabled, next_run, deferred });
+
+        // Send the current wallpaper's details when it changes
+        let current_path = state.read().await.current_path.clone();
+        if current_path != reported_path {
+            let info = current_path.as_deref().and_then(metadata::lookup);
+            let _ = event_tx.send(ServiceEvent::CurrentWallpaper(info));
+            reported_path = current_path;
+        }
 
         // Sleep 500ms before the next iteration to avoid busy-waiting
         tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
     }
 }
 
+/// Goes back to the wallpaper shown before the last change and records it
+/// as the current one, so the popup shows its details.
+///
+/// # Returns
+/// * `Ok(message)` - Success message like "Restored: Mountain Sunrise"
+/// * `Err(Error)` - Nothing to go back to, or applying it failed
+async fn do_revert(state: &Arc<RwLock<ServiceState>>) -> Result<String, Error> {
+    let path = crate::service::revert_cosmic_wallpaper()
+        .await
+        .map_err(|e| e.context("Failed to undo"))?;
+    let title = path.as_deref()
+        .and_then(metadata::lookup)
+        .map(|m| m.display_title().to_string())
+        .unwrap_or_else(|| "previous wallpaper".to_string());
+    state.write().await.current_path = path;
+    Ok(format!("Restored: {title}"))
+}
+
 /// Performs the complete wallpaper fetch-and-apply workflow.
 ///
 /// This is the core operation of the app. It:
 /// 1. Reloads config from disk (in case the user changed market/directory in settings)
-/// 2. Calls the Bing API to get today's image metadata
+/// 2. Asks the configured source (Bing or a local folder) for today's image metadata
 /// 3. Downloads the image to the wallpaper directory
+///    (steps 2-3 are retried on transient errors per the config's retry
+///    policy, calling `on_retry` before each wait)
 /// 4. Removes old wallpapers beyond the keep_days limit
 /// 5. Writes the COSMIC background config file to apply the wallpaper
 /// 6. Records the fetch time (so the timer knows not to catch up again today)
@@ -659,50 +802,72 @@ async fn run_background_service(
 ///
 /// # Returns
 /// * `Ok(message)` - Success message like "Applied: Mountain Sunrise"
-/// * `Err(message)` - Error message describing what went wrong
-async fn do_fetch_and_apply(state: &Arc<RwLock<ServiceState>>) -> Result<String, String> {
+/// * `Err(Error)` - What went wrong, prefixed with the step that failed
+async fn do_fetch_and_apply(
+    state: &Arc<RwLock<ServiceState>>,
+    on_retry: impl Fn(&Retry),
+) -> Result<String, Error> {
     // Reload config from disk to pick up any changes made in the settings window
     // (the settings window writes config.json directly, not via D-Bus)
     let fresh_config = Config::load();
-    let (market, wallpaper_dir, keep_days) = (
-        fresh_config.market.clone(),
-        fresh_config.wallpaper_dir.clone(),
-        fresh_config.keep_days,
-    );
+    let (wallpaper_dir, retry_policy) = (fresh_config.wallpaper_dir.clone(), fresh_config.retry.clone());
+    let network = crate::network::status_for(fresh_config.metered).await;
+    let source = crate::source::from_config(&fresh_config, network);
 
     // Update the shared state with the fresh config
     {
         let mut s = state.write().await;
-        s.config = fresh_config;
+        s.config = fresh_config.clone();
     }
 
-    // Step 1: Ask Bing's API for today's image info (title, URL, copyright)
-    let image = crate::bing::fetch_bing_image_info(&market)
-        .await
-        .map_err(|e| format!("Failed to fetch: {e}"))?;
-
-    eprintln!("Found: {}", image.title);
-
-    // Step 2: Download the actual image file to the wallpaper directory
-    // (skips download if the file already exists from a previous fetch today)
-    let path = crate::bing::download_image(&image, &wallpaper_dir, &market)
-        .await
-        .map_err(|e| format!("Failed to download: {e}"))?;
+    // Steps 1-2, retried on transient errors (e.g., the network isn't up yet after resume)
+    let (image, path) = retry::retry(
+        &retry_policy,
+        || async {
+            // Step 1: Ask the source for today's image info (title, URL, copyright)
+            let image = source.fetch_info()
+                .await
+                .map_err(|e| e.context("Failed to fetch"))?;
+
+            eprintln!("Found: {}", image.display_title());
+
+            // Step 2: Download the actual image file to the wallpaper directory
+            // (skips download if the file already exists from a previous fetch today)
+            let path = crate::source::download(source.as_ref(), &image, &wallpaper_dir, &crate::http::no_progress)
+                .await
+                .map_err(|e| e.context("Failed to download"))?;
+            Ok((image, path))
+        },
+        |r| {
+            eprintln!("{}", r.message());
+            on_retry(r);
+        },
+    ).await?;
 
     eprintln!("Downloaded to: {path}");
 
+    // Images for displays that show another market or day (see outputs.rs)
+    crate::outputs::fetch_images(&fresh_config, network, |r| {
+        eprintln!("{}", r.message());
+        on_retry(r);
+    }).await;
+
     // Step 3: Remove wallpapers older than keep_days to save disk space
-    crate::service::cleanup_old_wallpapers(&wallpaper_dir, keep_days);
+    crate::service::cleanup_old_wallpapers(&fresh_config);
 
-    // Step 4: Apply the wallpaper by writing the COSMIC background config
-    // and restarting the cosmic-bg process
+    // Step 4: Apply the wallpaper by writing the COSMIC background config,
+    // which cosmic-bg picks up
     crate::service::apply_cosmic_wallpaper(&path)
-        .map_err(|e| format!("Failed to apply: {e}"))?;
+        .await
+        .map_err(|e| e.context("Failed to apply"))?;
 
     // Step 5: Record this fetch so the timer's catch-up logic knows we're done for today
+    // (and remember the wallpaper so the popup can show its details)
     {
-        let s = state.read().await;
+        let mut s = state.write().await;
         s.timer.record_fetch();
+        s.current_image = Some(image.clone());
+        s.current_path = Some(path.clone());
     }
 
     // Step 6: Show a desktop notification to inform the user
@@ -711,11 +876,11 @@ async fn do_fetch_and_apply(state: &Arc<RwLock<ServiceState>>) -> Result<String,
             "-i",
             "preferences-desktop-wallpaper",   // Icon for the notification
             "Bing Wallpaper",                   // Notification title
-            &format!("Applied: {}", image.title), // Notification body
+            &format!("Applied: {}", image.display_title()), // Notification body
         ])
         .spawn();
 
-    Ok(format!("Applied: {}", image.title))
+    Ok(format!("Applied: {}", image.display_title()))
 }
 
 /// Entry point for running the panel applet.
@@ -0,0 +1,589 @@
+//! # COSMIC Background Config
+//!
+//! Reads and writes the config of `cosmic-bg`, COSMIC's background renderer,
+//! in `~/.config/cosmic/com.system76.CosmicBackground/v1/`. Every key is a
+//! file holding one RON (Rust Object Notation) value:
+//!
+//! | Key | Value |
+//! |-----|-------|
+//! | `all` | Background [`Entry`] for every display |
+//! | `same-on-all` | `true` to show `all` everywhere, `false` for per-display entries |
+//! | `backgrounds` | Names of the displays with their own entry, e.g. `["DP-1"]` |
+//! | `output.DP-1` | Background [`Entry`] for that display |
+//!
+//! An entry looks like this:
+//! ```text
+//! (
+//!     output: "all",
+//!     source: Path("/home/user/Pictures/BingWallpapers/bing-en-US-2026-02-05.jpg"),
+//!     filter_by_theme: false,
+//!     rotation_frequency: 300,
+//!     filter_method: Lanczos,
+//!     scaling_mode: Zoom,
+//!     sampling_method: Alphanumeric,
+//! )
+//! ```
+//!
+//! Setting a wallpaper changes only the existing entry's `source`, so
+//! options chosen in COSMIC Settings (e.g., Fit with a background color)
+//! survive. [`Config::scaling_mode`] and [`Config::filter_method`] override
+//! those two options when they aren't `Keep`. Values are written with the
+//! `ron` serializer, so paths containing quotes or backslashes come out
+//! correctly escaped.
+//!
+//! Only the fields this app changes are ever parsed. Every other field is
+//! kept as the RON text it was written as and goes back out unchanged, so
+//! options a newer cosmic-bg adds, renames or reshapes aren't lost. Only an
+//! entry that isn't a RON struct at all is replaced by a default one rather
+//! than blocking the wallpaper change.
+//!
+//! ## Writing
+//! Changes are written through a [`Backend`]: normally cosmic-config,
+//! COSMIC's own config library, whose atomic writes cosmic-bg watches and
+//! switches to without restarting. Inside the Flatpak sandbox cosmic-config
+//! would write to the sandbox's config directory instead of the host's, so
+//! there (and whenever cosmic-config fails) the files are written directly
+//! and cosmic-bg is restarted to pick them up (see
+//! [`crate::service::apply_cosmic_wallpaper`]).
+//!
+//! ## Rollback and Undo
+//! Before each change, the current settings of the keys it writes are taken
+//! as a [`Snapshot`]. After writing, the config is read back and checked; if
+//! that fails, or cosmic-bg doesn't come back up, the snapshot is written
+//! back. Otherwise it is kept in `previous_wallpaper.json` in the app's config
+//! directory, so the change can be undone later (applet Undo button,
+//! `--revert`, or the `RevertWallpaper` D-Bus method).
+
+use std::path::{Path, PathBuf};
+
+use cosmic::cosmic_config::{self, ConfigSet};
+use ron::value::RawValue;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{self, Config};
+use crate::error::Error;
+
+/// cosmic-bg's config name and version, as used by cosmic-config.
+const CONFIG_NAME: &str = "com.system76.CosmicBackground";
+const CONFIG_VERSION: u64 = 1;
+
+/// A background entry: what one display (or all of them) shows.
+///
+/// Holds every field as written, in order, as (name, RON value) pairs; the
+/// ones this app reads or changes are parsed on demand (see the
+/// [module docs](self)). Serialized, it is that RON struct; in JSON (the
+/// [undo snapshot](Snapshot::remember)) it is the RON text as a string.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// "all", or a display name like "DP-1"
+    pub output: String,
+    /// Every field, `output` included, with its RON value
+    fields: Vec<(String, String)>,
+}
+
+fn default_rotation_frequency() -> u64 {
+    300
+}
+
+/// What a background shows.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Source {
+    /// An image, or a folder of images for a slideshow
+    Path(PathBuf),
+    /// A solid color or gradient
+    Color(Color),
+}
+
+/// A background color.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Color {
+    Single([f32; 3]),
+    Gradient(Gradient),
+}
+
+/// A background gradient.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Gradient {
+    pub colors: Vec<[f32; 3]>,
+    pub radius: f32,
+}
+
+/// cosmic-bg's resampling filter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum CosmicFilterMethod {
+    Nearest,
+    Linear,
+    #[default]
+    Lanczos,
+}
+
+/// cosmic-bg's scaling mode; Fit fills the bars with a color.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum CosmicScalingMode {
+    Fit([f32; 3]),
+    Stretch,
+    #[default]
+    Zoom,
+}
+
+/// Order of a slideshow folder's images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SamplingMethod {
+    #[default]
+    Alphanumeric,
+    Random,
+}
+
+impl Entry {
+    /// The entry written when there is none yet: zoomed to fill, Lanczos.
+    pub fn new(output: &str, image_path: &str) -> Self {
+        let mut entry = Self { output: output.to_string(), fields: Vec::new() };
+        entry.set_field("output", &output);
+        entry.set_field("source", &Source::Path(PathBuf::from(image_path)));
+        entry.set_field("filter_by_theme", &false);
+        entry.set_field("rotation_frequency", &default_rotation_frequency());
+        entry.set_field("filter_method", &CosmicFilterMethod::default());
+        entry.set_field("scaling_mode", &CosmicScalingMode::default());
+        entry.set_field("sampling_method", &SamplingMethod::default());
+        entry
+    }
+
+    /// Parses an entry from RON. Only `output` has to be there and parse.
+    fn from_ron(ron: &str) -> Result<Self, String> {
+        let Fields(fields) = ron::from_str(ron).map_err(|e| e.to_string())?;
+        let output = fields
+            .iter()
+            .find(|(name, _)| name == "output")
+            .and_then(|(_, value)| ron::from_str(value).ok())
+            .ok_or_else(|| "background entry has no output".to_string())?;
+        Ok(Self { output, fields })
+    }
+
+    /// The entry as a RON struct, laid out the way COSMIC writes it.
+    fn to_ron(&self) -> String {
+        let mut ron = String::from("(\n");
+        for (name, value) in &self.fields {
+            ron.push_str(&format!("    {name}: {value},\n"));
+        }
+        ron.push(')');
+        ron
+    }
+
+    /// The same entry for another display.
+    fn for_output(mut self, output: &str) -> Self {
+        self.output = output.to_string();
+        self.set_field("output", &output);
+        self
+    }
+
+    /// Parses one field. None if it is missing or has a shape this app doesn't know.
+    pub fn field<T: serde::de::DeserializeOwned>(&self, name: &str) -> Option<T> {
+        let (_, value) = self.fields.iter().find(|(field, _)| field == name)?;
+        ron::from_str(value).ok()
+    }
+
+    /// Sets one field, in its place if the entry has it.
+    fn set_field<T: Serialize>(&mut self, name: &str, value: &T) {
+        let Ok(value) = ron::to_string(value) else {
+            return;
+        };
+        match self.fields.iter_mut().find(|(field, _)| field == name) {
+            Some((_, current)) => *current = value,
+            None => self.fields.push((name.to_string(), value)),
+        }
+    }
+
+    /// The image path, if the source is one.
+    pub fn image_path(&self) -> Option<PathBuf> {
+        match self.field("source")? {
+            Source::Path(path) => Some(path),
+            Source::Color(_) => None,
+        }
+    }
+
+    /// Points the entry at `image_path` and applies the scaling and filter
+    /// settings from `config` (unless they are `Keep`). Everything else is left as is.
+    pub fn set_image(&mut self, image_path: &str, config: &Config) {
+        self.set_field("source", &Source::Path(PathBuf::from(image_path)));
+        let scaling_mode = match (config.scaling_mode, self.field("scaling_mode")) {
+            (config::ScalingMode::Keep, _) => None,
+            // Keep the bar color chosen in COSMIC Settings
+            (config::ScalingMode::Fit, Some(CosmicScalingMode::Fit(color))) => Some(CosmicScalingMode::Fit(color)),
+            (config::ScalingMode::Fit, _) => Some(CosmicScalingMode::Fit([0.0, 0.0, 0.0])),
+            (config::ScalingMode::Zoom, _) => Some(CosmicScalingMode::Zoom),
+            (config::ScalingMode::Stretch, _) => Some(CosmicScalingMode::Stretch),
+        };
+        if let Some(scaling_mode) = scaling_mode {
+            self.set_field("scaling_mode", &scaling_mode);
+        }
+        let filter_method = match config.filter_method {
+            config::FilterMethod::Keep => None,
+            config::FilterMethod::Nearest => Some(CosmicFilterMethod::Nearest),
+            config::FilterMethod::Linear => Some(CosmicFilterMethod::Linear),
+            config::FilterMethod::Lanczos => Some(CosmicFilterMethod::Lanczos),
+        };
+        if let Some(filter_method) = filter_method {
+            self.set_field("filter_method", &filter_method);
+        }
+    }
+}
+
+impl Serialize for Entry {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // Written verbatim by the RON serializer, as a string by others
+        let raw = RawValue::from_boxed_ron(self.to_ron().into_boxed_str()).map_err(serde::ser::Error::custom)?;
+        raw.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Entry {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = Box::<RawValue>::deserialize(deserializer)?;
+        Entry::from_ron(raw.get_ron()).map_err(serde::de::Error::custom)
+    }
+}
+
+/// A RON struct's fields as (name, RON value) pairs, in order.
+struct Fields(Vec<(String, String)>);
+
+impl<'de> Deserialize<'de> for Fields {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FieldsVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for FieldsVisitor {
+            type Value = Fields;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a background entry")
+            }
+
+            fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<Fields, A::Error> {
+                let mut fields = Vec::new();
+                while let Some(name) = map.next_key::<String>()? {
+                    let value: Box<RawValue> = map.next_value()?;
+                    fields.push((name, value.get_ron().trim().to_string()));
+                }
+                Ok(Fields(fields))
+            }
+        }
+
+        deserializer.deserialize_struct("Entry", &[], FieldsVisitor)
+    }
+}
+
+/// Directory of COSMIC's background config (see the [module docs](self)).
+///
+/// Natively this is where cosmic-config keeps it, under the XDG config
+/// directory. In Flatpak we use home_dir() instead, because there
+/// config_dir() returns the sandboxed path (~/.var/app/APP_ID/config/),
+/// but COSMIC reads from the real ~/.config/ on the host.
+pub fn config_dir() -> Option<PathBuf> {
+    let base = if crate::service::is_flatpak() { dirs::home_dir()?.join(".config") } else { dirs::config_dir()? };
+    Some(base.join(format!("cosmic/{CONFIG_NAME}/v{CONFIG_VERSION}")))
+}
+
+/// Reads and parses one key. None if it is missing or doesn't parse.
+pub fn read_key<T: serde::de::DeserializeOwned>(key: &str) -> Option<T> {
+    read_key_in(&config_dir()?, key)
+}
+
+/// Reads and parses one key from the config in `dir`.
+fn read_key_in<T: serde::de::DeserializeOwned>(dir: &Path, key: &str) -> Option<T> {
+    let content = std::fs::read_to_string(dir.join(key)).ok()?;
+    match ron::from_str(&content) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            eprintln!("Ignoring unreadable COSMIC background setting '{key}': {e}");
+            None
+        }
+    }
+}
+
+/// How changed keys reach cosmic-bg (see the [module docs](self#writing)).
+pub enum Backend {
+    /// Through cosmic-config; cosmic-bg switches to the change by itself
+    CosmicConfig(cosmic_config::Config),
+    /// Straight to the files in this directory; cosmic-bg has to be restarted to notice
+    Files(PathBuf),
+}
+
+impl Backend {
+    /// The cosmic-config backend, or None where it can't reach the host's
+    /// config (Flatpak) or fails to open it.
+    pub fn cosmic_config() -> Option<Self> {
+        if crate::service::is_flatpak() {
+            return None;
+        }
+        match cosmic_config::Config::new(CONFIG_NAME, CONFIG_VERSION) {
+            Ok(config) => Some(Backend::CosmicConfig(config)),
+            Err(e) => {
+                eprintln!("Failed to open COSMIC's background config: {e}");
+                None
+            }
+        }
+    }
+
+    /// Writes one key.
+    pub fn write_key<T: Serialize>(&self, key: &str, value: &T) -> Result<(), Error> {
+        match self {
+            Backend::CosmicConfig(config) => config
+                .set(key, value)
+                .map_err(|e| Error::Config(format!("Failed to write COSMIC background setting '{key}': {e}"))),
+            Backend::Files(dir) => write_file(dir, key, value),
+        }
+    }
+}
+
+/// Writes one key's file, creating the config directory if needed.
+fn write_file<T: Serialize>(dir: &Path, key: &str, value: &T) -> Result<(), Error> {
+    std::fs::create_dir_all(dir).map_err(|e| Error::Io(format!("Failed to create config dir: {e}")))?;
+    std::fs::write(dir.join(key), to_ron(value)?).map_err(|e| Error::Io(format!("Failed to write config: {e}")))
+}
+
+/// Serializes a value the way COSMIC writes its config.
+fn to_ron<T: Serialize>(value: &T) -> Result<String, Error> {
+    ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::new())
+        .map_err(|e| Error::Config(format!("Failed to encode background config: {e}")))
+}
+
+/// The key holding `output`'s entry ("all", or `output.DP-1`).
+pub fn entry_key(output: &str) -> String {
+    if output == "all" { output.to_string() } else { format!("output.{output}") }
+}
+
+/// The entry that shows `image_path` on `output`, keeping the rest of its
+/// current entry. A display without an entry of its own starts from the
+/// `all` entry's options.
+fn entry_for(dir: &Path, output: &str, image_path: &str, config: &Config) -> Entry {
+    let mut entry = read_key_in::<Entry>(dir, &entry_key(output))
+        .or_else(|| read_key_in::<Entry>(dir, "all").map(|all| all.for_output(output)))
+        .unwrap_or_else(|| Entry::new(output, image_path));
+    entry.set_image(image_path, config);
+    entry
+}
+
+/// The image COSMIC currently shows on all displays (the `all` entry), or
+/// None if it isn't set to a single image.
+pub fn applied_wallpaper() -> Option<String> {
+    let entry = read_key::<Entry>("all")?;
+    Some(entry.image_path()?.to_str()?.to_string())
+}
+
+/// Everything a wallpaper change writes: the entries it sets and which
+/// displays use their own.
+///
+/// Besides describing a change, a snapshot of the settings taken just before
+/// it is what a failed change is rolled back to, and is kept on disk (see
+/// [`Snapshot::remember`]) so the last change can be undone.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Entries to write, the `all` entry first
+    pub entries: Vec<Entry>,
+    /// Displays with their own entry (the `backgrounds` key)
+    pub backgrounds: Vec<String>,
+    /// Whether `all` is shown everywhere (the `same-on-all` key)
+    pub same_on_all: bool,
+}
+
+impl Snapshot {
+    /// The settings that show `main` on every display, except those in
+    /// `assignments`, which get their own (output name, image path).
+    pub fn for_images(dir: &Path, main: &str, assignments: &[(String, String)], config: &Config) -> Self {
+        let mut entries = vec![entry_for(dir, "all", main, config)];
+        entries.extend(assignments.iter().map(|(output, path)| entry_for(dir, output, path, config)));
+        // The user's own list is left alone when every display shows the same
+        let backgrounds = if assignments.is_empty() {
+            read_key_in(dir, "backgrounds").unwrap_or_default()
+        } else {
+            assignments.iter().map(|(output, _)| output.clone()).collect()
+        };
+        Self { entries, backgrounds, same_on_all: assignments.is_empty() }
+    }
+
+    /// The current settings of the keys `target` writes. Entries that don't
+    /// exist yet are left out; once `backgrounds` and `same-on-all` are back,
+    /// COSMIC ignores them anyway.
+    pub fn current(dir: &Path, target: &Snapshot) -> Self {
+        Self {
+            entries: target.entries
+                .iter()
+                .filter_map(|entry| read_key_in(dir, &entry_key(&entry.output)))
+                .collect(),
+            backgrounds: read_key_in(dir, "backgrounds").unwrap_or_default(),
+            same_on_all: read_key_in(dir, "same-on-all").unwrap_or(true),
+        }
+    }
+
+    /// Writes every key, display entries first so `all` and `same-on-all`
+    /// only switch over once they are in place.
+    pub fn write(&self, backend: &Backend) -> Result<(), Error> {
+        for entry in self.entries.iter().rev() {
+            backend.write_key(&entry_key(&entry.output), entry)?;
+        }
+        backend.write_key("backgrounds", &self.backgrounds)?;
+        backend.write_key("same-on-all", &self.same_on_all)
+    }
+
+    /// Reads the config in `dir` back and checks that it holds this snapshot.
+    pub fn verify(&self, dir: &Path) -> Result<(), Error> {
+        let mismatch = |key: &str| Error::Config(format!("COSMIC's background setting '{key}' didn't read back as written"));
+        for entry in &self.entries {
+            let key = entry_key(&entry.output);
+            if read_key_in::<Entry>(dir, &key).as_ref() != Some(entry) {
+                return Err(mismatch(&key));
+            }
+        }
+        if read_key_in::<Vec<String>>(dir, "backgrounds").unwrap_or_default() != self.backgrounds {
+            return Err(mismatch("backgrounds"));
+        }
+        if read_key_in::<bool>(dir, "same-on-all").unwrap_or(true) != self.same_on_all {
+            return Err(mismatch("same-on-all"));
+        }
+        Ok(())
+    }
+
+    /// The image shown on all displays (the `all` entry's), if it is one.
+    pub fn image_path(&self) -> Option<PathBuf> {
+        self.entries.iter().find(|entry| entry.output == "all")?.image_path()
+    }
+
+    /// An image this snapshot shows that no longer exists (e.g., removed by cleanup).
+    pub fn missing_image(&self) -> Option<PathBuf> {
+        self.entries.iter().filter_map(Entry::image_path).find(|path| !path.exists())
+    }
+
+    /// Where the snapshot to undo to is kept.
+    pub fn previous_path() -> Option<PathBuf> {
+        config::app_config_dir().map(|dir| dir.join("previous_wallpaper.json"))
+    }
+
+    /// Keeps this snapshot in `path` (usually [`Snapshot::previous_path`]) as
+    /// the one [`Snapshot::previous`] returns.
+    pub fn remember(&self, path: &Path) -> Result<(), Error> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| Error::Io(format!("Failed to create config dir: {e}")))?;
+        }
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| Error::Config(format!("Failed to encode the previous wallpaper: {e}")))?;
+        std::fs::write(path, content).map_err(|e| Error::Io(format!("Failed to save the previous wallpaper: {e}")))
+    }
+
+    /// The background settings from before the last wallpaper change, if any,
+    /// as kept in `path`.
+    pub fn previous(path: &Path) -> Option<Self> {
+        let content = std::fs::read_to_string(path).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// As written by COSMIC Settings: Fit with a gray background, a slideshow interval of 10 minutes
+    const COSMIC_ENTRY: &str = r#"(
+    output: "all",
+    source: Path("/usr/share/backgrounds/pop/kate-hazen-COSMIC-desktop-wallpaper.png"),
+    filter_by_theme: true,
+    rotation_frequency: 600,
+    filter_method: Linear,
+    scaling_mode: Fit((0.2, 0.2, 0.2)),
+    sampling_method: Random,
+)"#;
+
+    #[test]
+    fn test_set_image_keeps_cosmic_options() {
+        let mut entry: Entry = ron::from_str(COSMIC_ENTRY).unwrap();
+        entry.set_image("/pictures/bing.jpg", &Config::default());
+        assert_eq!(entry.image_path(), Some(PathBuf::from("/pictures/bing.jpg")));
+        assert_eq!(entry.field("scaling_mode"), Some(CosmicScalingMode::Fit([0.2, 0.2, 0.2])));
+        assert_eq!(entry.field("filter_method"), Some(CosmicFilterMethod::Linear));
+        assert_eq!((entry.field("filter_by_theme"), entry.field("rotation_frequency")), (Some(true), Some(600)));
+        assert_eq!(entry.field("sampling_method"), Some(SamplingMethod::Random));
+
+        // Options set in our config win
+        let config = Config {
+            scaling_mode: config::ScalingMode::Zoom,
+            filter_method: config::FilterMethod::Lanczos,
+            ..Config::default()
+        };
+        entry.set_image("/pictures/bing.jpg", &config);
+        assert_eq!(entry.field("scaling_mode"), Some(CosmicScalingMode::Zoom));
+        assert_eq!(entry.field("filter_method"), Some(CosmicFilterMethod::Lanczos));
+    }
+
+    #[test]
+    fn test_fields_this_app_doesnt_know_are_kept() {
+        // As a newer cosmic-bg might write it: a new field, and a reshaped one
+        let newer = r#"(
+    output: "all",
+    source: Path("/usr/share/backgrounds/old.png"),
+    filter_by_theme: true,
+    rotation_frequency: 600,
+    filter_method: Linear,
+    scaling_mode: Fit((0.2, 0.2, 0.2)),
+    sampling_method: Shuffle(seed: 7),
+    blur: Some(Gaussian(radius: 4.0)),
+)"#;
+        let mut entry: Entry = ron::from_str(newer).unwrap();
+        entry.set_image("/pictures/bing.jpg", &Config::default());
+
+        let expected = newer.replace("/usr/share/backgrounds/old.png", "/pictures/bing.jpg");
+        assert_eq!(to_ron(&entry).unwrap(), expected);
+        assert_eq!(entry.field::<SamplingMethod>("sampling_method"), None);
+
+        // The undo snapshot keeps them too
+        let json = serde_json::to_string(&entry).unwrap();
+        assert_eq!(serde_json::from_str::<Entry>(&json).unwrap(), entry);
+    }
+
+    #[test]
+    fn test_snapshot_rolls_back() {
+        let dir = std::env::temp_dir().join(format!("bing-wallpaper-background-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("all"), COSMIC_ENTRY).unwrap();
+        std::fs::write(dir.join("backgrounds"), r#"["DP-1"]"#).unwrap();
+        std::fs::write(dir.join("same-on-all"), "false").unwrap();
+        let files = Backend::Files(dir.clone());
+
+        let target = Snapshot::for_images(&dir, "/pictures/bing.jpg", &[], &Config::default());
+        let previous = Snapshot::current(&dir, &target);
+        // The user's per-display list stays, just unused
+        assert_eq!((target.backgrounds.as_slice(), target.same_on_all), (["DP-1".to_string()].as_slice(), true));
+
+        target.write(&files).unwrap();
+        target.verify(&dir).unwrap();
+        assert!(previous.verify(&dir).is_err());
+        assert_eq!(target.image_path(), Some(PathBuf::from("/pictures/bing.jpg")));
+
+        // Rolling back brings the old entry and keys back exactly
+        previous.write(&files).unwrap();
+        previous.verify(&dir).unwrap();
+        assert_eq!(
+            read_key_in::<Entry>(&dir, "all").unwrap().image_path(),
+            Some(PathBuf::from("/usr/share/backgrounds/pop/kate-hazen-COSMIC-desktop-wallpaper.png"))
+        );
+        assert_eq!(read_key_in::<bool>(&dir, "same-on-all"), Some(false));
+
+        // A key that doesn't read back fails the check
+        std::fs::write(dir.join("all"), "(output: \"all\"").unwrap();
+        assert!(previous.verify(&dir).is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_paths_are_escaped() {
+        let path = r#"/pictures/"quoted" \ back\slash.jpg"#;
+        let written = to_ron(&Entry::new("all", path)).unwrap();
+        let read: Entry = ron::from_str(&written).unwrap();
+        assert_eq!(read.image_path(), Some(PathBuf::from(path)));
+
+        // Color sources and minimal entries parse too
+        let color: Entry = ron::from_str(r#"(output: "DP-1", source: Color(Single((0.0, 0.5, 1.0))))"#).unwrap();
+        assert_eq!(color.image_path(), None);
+        assert_eq!(color.field::<CosmicScalingMode>("scaling_mode"), None);
+        assert!(ron::from_str::<Entry>("[1, 2]").is_err());
+    }
+}
@@ -6,37 +6,80 @@
 //! ## API Endpoint
 //! The Bing API is accessed at:
 //! ```text
-//! https://www.bing.com/HPImageArchive.aspx?format=js&idx=0&n=1&mkt=<market>
+//! {host}/HPImageArchive.aspx?format=js&idx=0&n=1&mkt=<market>
 //! ```
+//! The host comes from [`Config::bing_host`](crate::config::Config::bing_host)
+//! and defaults to `https://www.bing.com`. A custom host (e.g., a local mirror
+//! or mock server) is used as-is. With the default host, failed requests are
+//! retried against `https://cn.bing.com`, which is what mainland China can
+//! reach: always for `zh-CN`, and for other markets when `www.bing.com` is
+//! unreachable.
 //!
 //! Parameters:
 //! - `format=js`: Return JSON response
 //! - `idx=0`: Start from today's image (0=today, 1=yesterday, etc.)
-//! - `n=1`: Number of images to return
+//! - `n=1`: Number of images to return (Bing serves at most 8)
 //! - `mkt=<market>`: Regional market code (e.g., "en-US")
 //!
+//! ## Archive
+//! Bing keeps roughly the last 8 days available through the same endpoint.
+//! [`fetch_bing_archive`] exposes `idx`/`n` directly, and [`backfill_archive`]
+//! uses it to download any of those days missing from the wallpaper directory.
+//!
+//! ## Captions
+//! Titles and copyrights come in the market's language. With a separate
+//! [caption market](crate::config::Config::caption_market), the same photo is
+//! looked up in that market's archive as well ([`fetch_caption`]) and its
+//! strings are stored next to the originals, so e.g. the `en-GB` image can be
+//! shown with a German title.
+//!
 //! ## Image URLs
-//! The API returns partial URLs that need `https://www.bing.com` prepended.
-//! Images are typically available in multiple resolutions; this client uses
-//! the default high-resolution version (1920x1080).
+//! The API returns partial URLs that need the host that answered prepended.
+//! The `url` field points at the default 1920x1080 JPEG, while `urlbase`
+//! plus a size suffix selects other variants:
+//! ```text
+//! {urlbase}_UHD.jpg        {urlbase}_1920x1080.jpg    {urlbase}_1080x1920.jpg
+//! ```
+//! Downloads try the configured [`Resolution`] first and fall back to the
+//! next size down when a variant returns 404. On a metered connection with
+//! the `reduce` [metered policy](crate::config::MeteredPolicy), they ask for
+//! a [smaller size](Resolution::reduced) instead. The network state comes
+//! from the caller (see [`crate::network::status_for`]), as do the proxy and
+//! certificate [settings](NetworkSettings), so nothing here asks
+//! NetworkManager or reads the config file.
 
 use serde::Deserialize;
 use std::path::Path;
-use std::time::Duration;
 
-/// Base URL for the Bing Homepage Image Archive API.
-const BING_API_URL: &str = "https://www.bing.com/HPImageArchive.aspx";
+use crate::config::{Config, MeteredPolicy, Resolution, RetryPolicy};
+use crate::error::Error;
+use crate::http::{self, DownloadStatus, NetworkSettings, Progress};
+use crate::metadata::ImageMetadata;
+use crate::naming::FileNaming;
+use crate::network::NetworkStatus;
+use crate::retry::{self, Retry};
+use crate::source::{self, SourceFuture, WallpaperSource};
 
-/// HTTP request timeout in seconds
-const REQUEST_TIMEOUT_SECS: u64 = 30;
+/// Default Bing host, used unless the config names another one.
+pub const DEFAULT_HOST: &str = "https://www.bing.com";
 
-/// Creates an HTTP client with appropriate timeout settings.
-fn create_client() -> Result<reqwest::Client, String> {
-    reqwest::Client::builder()
-        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {e}"))
-}
+/// Bing's mainland China host, tried when the default host fails.
+const CN_HOST: &str = "https://cn.bing.com";
+
+/// Path of the Homepage Image Archive API on a Bing host.
+const API_PATH: &str = "/HPImageArchive.aspx";
+
+/// Number of days Bing keeps in its archive (the API caps `n` at 8).
+pub const ARCHIVE_DAYS: u32 = 8;
+
+/// Bing's landscape size suffixes, largest first.
+const LANDSCAPE_SIZES: &[&str] = &[
+    "UHD", "1920x1200", "1920x1080", "1366x768", "1280x768", "1280x720",
+    "1024x768", "800x600", "800x480", "640x480",
+];
+
+/// Bing's portrait size suffixes, largest first.
+const PORTRAIT_SIZES: &[&str] = &["1080x1920", "768x1366", "768x1280", "720x1280", "480x800"];
 
 /// Raw API response from Bing.
 ///
@@ -54,43 +97,83 @@ pub struct BingApiResponse {
 /// using serde to match Rust naming conventions.
 #[derive(Debug, Clone, Deserialize)]
 pub struct BingImageData {
-    /// Partial URL path (needs `https://www.bing.com` prefix)
+    /// Partial URL path (needs the host prefix)
     pub url: String,
+    /// Partial URL path without the size suffix (e.g., "/th?id=OHR.Example_EN-US123")
+    #[serde(default)]
+    pub urlbase: String,
     /// Copyright/attribution text for the image
     pub copyright: String,
+    /// Link to a Bing search about the image (absolute or partial URL)
+    #[serde(rename = "copyrightlink", default)]
+    pub copyright_link: String,
     /// Image title/description
     pub title: String,
     /// Date when this image was featured (format: YYYYMMDD)
     #[serde(rename = "startdate")]
     pub start_date: String,
+    /// Date when this image stopped being featured (format: YYYYMMDD)
+    #[serde(rename = "enddate", default)]
+    pub end_date: String,
+    /// Bing's hash of the image file
+    #[serde(default)]
+    pub hsh: String,
 }
 
 /// Processed image information ready for use in the application.
 ///
 /// This is the canonical representation used throughout the app,
-/// with the URL already prefixed with the Bing domain.
+/// with the URL already prefixed with the Bing host.
 #[derive(Debug, Clone)]
 pub struct BingImage {
-    /// Full download URL for the image
+    /// Full download URL for the image (default 1920x1080 variant)
     pub url: String,
+    /// Full URL without the size suffix, used to build other resolutions
+    /// (empty if the API didn't provide one)
+    pub urlbase: String,
     /// Copyright/attribution text
     pub copyright: String,
+    /// Full link to more information about the image (empty if none)
+    pub copyright_link: String,
     /// Image title/description
     pub title: String,
     /// Feature date (format: YYYYMMDD) - used in download_image for filename
     pub date: String,
+    /// End of the feature period (format: YYYYMMDD)
+    pub end_date: String,
+    /// Bing's hash of the image file
+    pub hsh: String,
+    /// Market this metadata was fetched for (e.g., "en-US")
+    pub market: String,
 }
 
-impl From<BingImageData> for BingImage {
+impl BingImage {
     /// Converts raw API data to the application's image format.
     ///
-    /// Prepends the Bing domain to the partial URL path.
-    fn from(data: BingImageData) -> Self {
+    /// Prepends `host` (the Bing host that answered) to the partial URL paths.
+    fn from_api(data: BingImageData, host: &str) -> Self {
         Self {
-            url: format!("https://www.bing.com{}", data.url),
+            url: format!("{host}{}", data.url),
+            urlbase: if data.urlbase.is_empty() {
+                String::new()
+            } else {
+                format!("{host}{}", data.urlbase)
+            },
             copyright: data.copyright,
+            // Bing sends "javascript:void(0)" when there is no link
+            copyright_link: if data.copyright_link.starts_with('/') {
+                format!("{host}{}", data.copyright_link)
+            } else if data.copyright_link.starts_with("http") {
+                data.copyright_link
+            } else {
+                String::new()
+            },
             title: data.title,
             date: data.start_date,
+            end_date: data.end_date,
+            hsh: data.hsh,
+            // Filled in by fetch_bing_archive, which knows the requested market
+            market: String::new(),
         }
     }
 }
@@ -101,121 +184,834 @@ impl From<BingImageData> for BingImage {
 /// in the specified regional market.
 ///
 /// # Arguments
+/// * `net` - Proxy, certificates and User-Agent for the request
+/// * `host` - Bing host from the config (e.g., "https://www.bing.com")
 /// * `market` - Regional market code (e.g., "en-US", "de-DE")
 ///
 /// # Returns
 /// * `Ok(BingImage)` - Image metadata including URL, title, and copyright
-/// * `Err(String)` - Error message if the API request or parsing fails
+/// * `Err(Error)` - Error if the API request or parsing fails
 ///
 /// # Example
 /// ```ignore
-/// let image = fetch_bing_image_info("en-US").await?;
+/// let image = fetch_bing_image_info(&NetworkSettings::default(), DEFAULT_HOST, "en-US").await?;
 /// println!("Today's image: {}", image.title);
 /// ```
-pub async fn fetch_bing_image_info(market: &str) -> Result<BingImage, String> {
+pub async fn fetch_bing_image_info(net: &NetworkSettings, host: &str, market: &str) -> Result<BingImage, Error> {
+    fetch_bing_archive(net, host, market, 0, 1)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| Error::InvalidResponse("No images in Bing response".to_string()))
+}
+
+/// Checks that Bing serves images for a market code before it is saved as a
+/// custom market.
+///
+/// Bing answers any well-formed code, falling back to its rest-of-world feed
+/// for markets it doesn't have, so the answer only counts if its `urlbase`
+/// names the requested market (see [`urlbase_market`]).
+///
+/// # Returns
+/// * `Ok(BingImage)` - Today's image for the market
+/// * `Err(Error)` - `Error::Config` if the code is malformed or Bing answered
+///   for another market, otherwise the error from the API request
+pub async fn probe_market(net: &NetworkSettings, host: &str, market: &str) -> Result<BingImage, Error> {
+    if !crate::config::is_valid_market_code(market) {
+        return Err(Error::Config(format!(
+            "Invalid market code '{market}': expected a language and region like \"en-US\""
+        )));
+    }
+    let image = fetch_bing_image_info(net, host, market)
+        .await
+        .map_err(|e| e.context(&format!("Bing didn't answer for market '{market}'")))?;
+    match urlbase_market(&image.urlbase) {
+        Some(served) if served.eq_ignore_ascii_case(market) => Ok(image),
+        served => Err(Error::Config(format!(
+            "Bing has no market '{market}': it answered with {}",
+            served.map_or("an unrecognized feed".to_string(), |s| format!("the {s} feed"))
+        ))),
+    }
+}
+
+/// Finds the same photo in another market's language.
+///
+/// Fetches the caption market's archive and matches `image` by Bing's hash,
+/// falling back to the photo ID in `urlbase` (see [`photo_id`]).
+///
+/// # Returns
+/// * `Ok(Some(BingImage))` - The photo as `caption_market` describes it
+/// * `Ok(None)` - The caption market doesn't feature this photo
+/// * `Err(Error)` - Error if the API request fails
+pub async fn fetch_caption(
+    net: &NetworkSettings,
+    host: &str,
+    caption_market: &str,
+    image: &BingImage,
+) -> Result<Option<BingImage>, Error> {
+    let captions = fetch_bing_archive(net, host, caption_market, 0, ARCHIVE_DAYS).await?;
+    Ok(find_caption(&captions, image).cloned())
+}
+
+/// Picks `image` out of another market's archive: by hash if both have one,
+/// otherwise by photo ID.
+fn find_caption<'a>(captions: &'a [BingImage], image: &BingImage) -> Option<&'a BingImage> {
+    captions
+        .iter()
+        .find(|c| !image.hsh.is_empty() && c.hsh == image.hsh)
+        .or_else(|| {
+            let id = photo_id(&image.urlbase)?;
+            captions.iter().find(|c| photo_id(&c.urlbase) == Some(id))
+        })
+}
+
+/// The market-independent part of a `urlbase`: "OHR.Name" for
+/// ".../th?id=OHR.Name_EN-US1234567890". None if it doesn't look like that.
+fn photo_id(urlbase: &str) -> Option<&str> {
+    let (_, id) = urlbase.split_once("id=")?;
+    let (name, _) = id.rsplit_once('_')?;
+    (!name.is_empty()).then_some(name)
+}
+
+/// The market a `urlbase` was served for: "EN-US" for
+/// ".../th?id=OHR.Name_EN-US1234567890", "ROW" for Bing's rest-of-world feed.
+/// None if it doesn't look like that.
+fn urlbase_market(urlbase: &str) -> Option<&str> {
+    let (_, suffix) = urlbase.split_once("id=")?.1.rsplit_once('_')?;
+    let market = suffix.trim_end_matches(|c: char| c.is_ascii_digit());
+    (!market.is_empty()).then_some(market)
+}
+
+/// Adds the caption in `caption_market`'s language to `info`, if it's set and
+/// differs from the image's own market.
+///
+/// Captions are a nicety: failures are logged and the image keeps its own.
+async fn add_caption(net: &NetworkSettings, host: &str, caption_market: &str, image: &BingImage, info: &mut ImageMetadata) {
+    if caption_market.is_empty() || caption_market.eq_ignore_ascii_case(&image.market) {
+        return;
+    }
+    match fetch_caption(net, host, caption_market, image).await {
+        Ok(Some(caption)) => info.set_caption(&caption),
+        Ok(None) => eprintln!("'{}' is not featured in {caption_market}, keeping the {} caption", image.title, image.market),
+        Err(e) => eprintln!("Failed to fetch the {caption_market} caption: {e}"),
+    }
+}
+
+/// Fetches a range of images from the Bing archive.
+///
+/// With the default host, a failed request is retried against `cn.bing.com`
+/// (see the [module docs](self)); a custom host is only ever tried alone.
+///
+/// # Arguments
+/// * `net` - Proxy, certificates and User-Agent for the request
+/// * `host` - Bing host from the config (e.g., "https://www.bing.com")
+/// * `market` - Regional market code (e.g., "en-US", "de-DE")
+/// * `idx` - Days back to start from (0 = today, 1 = yesterday, ...)
+/// * `n` - Number of images to return (Bing caps this at [`ARCHIVE_DAYS`])
+///
+/// # Returns
+/// * `Ok(Vec<BingImage>)` - Every image in the response, newest first
+/// * `Err(Error)` - Error if the API request or parsing fails
+pub async fn fetch_bing_archive(
+    net: &NetworkSettings,
+    host: &str,
+    market: &str,
+    idx: u32,
+    n: u32,
+) -> Result<Vec<BingImage>, Error> {
+    let host = normalize_host(host);
+    let error = match fetch_archive_from(net, &host, market, idx, n).await {
+        Ok(images) => return Ok(images),
+        Err(error) => error,
+    };
+
+    if host == DEFAULT_HOST && (error.is_unreachable() || market.eq_ignore_ascii_case("zh-CN")) {
+        eprintln!("{} failed ({}), trying {CN_HOST}", host, error);
+        return fetch_archive_from(net, CN_HOST, market, idx, n).await;
+    }
+
+    Err(error)
+}
+
+/// Queries one Bing host's archive API.
+async fn fetch_archive_from(net: &NetworkSettings, host: &str, market: &str, idx: u32, n: u32) -> Result<Vec<BingImage>, Error> {
     let url = format!(
-        "{}?format=js&idx=0&n=1&mkt={}",
-        BING_API_URL, market
+        "{}{}?format=js&idx={}&n={}&mkt={}",
+        host, API_PATH, idx, n, market
     );
 
-    let client = create_client()?;
+    let client = http::client(net)?;
     let response = client.get(&url)
         .send()
         .await
         .map_err(|e| {
             if e.is_timeout() {
-                "Request timed out - check your internet connection".to_string()
+                Error::Timeout("Request timed out - check your internet connection".to_string())
             } else {
-                format!("Failed to fetch Bing API: {e}")
+                Error::request(&e, format!("Failed to fetch Bing API: {e}"))
             }
         })?;
 
-    let api_response: BingApiResponse = response
-        .json()
+    if !response.status().is_success() {
+        let status = response.status();
+        return Err(Error::Http { status: status.as_u16(), message: format!("Bing API returned HTTP {status}") });
+    }
+
+    let body = response
+        .bytes()
         .await
-        .map_err(|e| format!("Failed to parse Bing response: {e}"))?;
+        .map_err(|e| Error::request(&e, format!("Failed to read Bing response: {e}")))?;
+    let api_response: BingApiResponse = serde_json::from_slice(&body)
+        .map_err(|e| Error::InvalidResponse(format!("Failed to parse Bing response: {e}")))?;
+
+    if api_response.images.is_empty() {
+        return Err(Error::InvalidResponse("No images in Bing response".to_string()));
+    }
+
+    Ok(api_response.images
+        .into_iter()
+        .map(|data| BingImage { market: market.to_string(), ..BingImage::from_api(data, host) })
+        .collect())
+}
+
+/// Cleans up a configured host: adds `https://` if no scheme was given and
+/// drops trailing slashes. An empty value means the default host.
+fn normalize_host(host: &str) -> String {
+    let host = host.trim().trim_end_matches('/');
+    if host.is_empty() {
+        DEFAULT_HOST.to_string()
+    } else if host.contains("://") {
+        host.to_string()
+    } else {
+        format!("https://{host}")
+    }
+}
+
+/// Where archived days come from and how they are stored, for
+/// [`backfill_archive`] and [`download_day`].
+#[derive(Debug, Clone)]
+pub struct ArchiveSettings {
+    /// Bing host to query (e.g., "https://www.bing.com")
+    pub host: String,
+    /// Market whose language captions are shown in (empty for the image's own)
+    pub caption_market: String,
+    /// How images are named in the wallpaper directory
+    pub naming: FileNaming,
+    /// Directory the images are stored in
+    pub wallpaper_dir: String,
+    /// Preferred image size
+    pub resolution: Resolution,
+    /// How failed requests are retried
+    pub retry: RetryPolicy,
+    /// Days images are kept before cleanup deletes them (0 = forever)
+    pub keep_days: u32,
+    /// What to do on a metered connection
+    pub metered: MeteredPolicy,
+    /// The network state, for the metered policy
+    pub network: NetworkStatus,
+    /// Proxy, certificates and User-Agent for requests
+    pub net: NetworkSettings,
+}
+
+impl ArchiveSettings {
+    /// The settings configured by the user, for downloading over `network`
+    /// (see [`crate::network::status_for`]).
+    pub fn from_config(config: &Config, network: NetworkStatus) -> Self {
+        Self {
+            host: config.bing_host.clone(),
+            caption_market: config.caption_market.clone(),
+            naming: FileNaming::from_config(config),
+            wallpaper_dir: config.wallpaper_dir.clone(),
+            resolution: config.resolution.clone(),
+            retry: config.retry.clone(),
+            keep_days: config.keep_days,
+            metered: config.metered,
+            network,
+            net: NetworkSettings::from_config(config),
+        }
+    }
+
+    /// A Bing source for `market` that downloads with these settings.
+    fn source(&self, market: &str) -> BingSource {
+        // The image URLs are already absolute, so the host isn't needed here
+        BingSource::new(DEFAULT_HOST, market, self.resolution.clone())
+            .with_network(self.metered, self.network)
+            .with_network_settings(self.net.clone())
+    }
+}
+
+/// What [`backfill_archive`] downloaded, and the days it couldn't.
+#[derive(Debug, Default)]
+pub struct Backfill {
+    /// Paths of the newly downloaded images (empty if nothing was missing)
+    pub downloaded: Vec<String>,
+    /// Days whose image couldn't be downloaded: (date as YYYY-MM-DD, error)
+    pub failed: Vec<(String, Error)>,
+}
+
+impl Backfill {
+    /// One line listing the failed days and why, or None if every day worked.
+    pub fn error_summary(&self) -> Option<String> {
+        if self.failed.is_empty() {
+            return None;
+        }
+        let days: Vec<String> = self.failed.iter().map(|(date, e)| format!("{date} ({e})")).collect();
+        Some(format!("Failed to download {} day(s): {}", self.failed.len(), days.join(", ")))
+    }
+}
+
+/// Downloads every archived day that is missing from the wallpaper directory.
+///
+/// Fetches the full [`ARCHIVE_DAYS`] window from Bing and downloads each image
+/// that is not stored yet (under this market's filename or, via Bing's hash,
+/// another market's), so machines that were offline for a few days end up
+/// with a complete daily history. With a caption market, each new image
+/// also gets its caption in that language. Days older than `keep_days` are
+/// skipped, since [cleanup](crate::service::cleanup_old_wallpapers) would
+/// delete them right away.
+///
+/// The archive request and each day's download are retried by the
+/// [retry policy](crate::retry), with `on_retry` told before each wait. A day
+/// that still fails is logged and skipped, so one missing variant doesn't
+/// cost the rest of the window.
+///
+/// # Returns
+/// * `Ok(Backfill)` - The newly downloaded images and the days that failed
+/// * `Err(Error)` - Error if the archive request fails
+pub async fn backfill_archive(
+    settings: &ArchiveSettings,
+    market: &str,
+    on_retry: impl Fn(&Retry),
+) -> Result<Backfill, Error> {
+    let ArchiveSettings { host, caption_market, naming, wallpaper_dir, resolution, retry: policy, keep_days, net, .. } = settings;
+    let images = retry::retry(policy, || fetch_bing_archive(net, host, market, 0, ARCHIVE_DAYS), &on_retry).await?;
+
+    // One archive request covers the captions for the whole window
+    let captions = if caption_market.is_empty() || caption_market.eq_ignore_ascii_case(market) {
+        Vec::new()
+    } else {
+        fetch_bing_archive(net, host, caption_market, 0, ARCHIVE_DAYS)
+            .await
+            .unwrap_or_else(|e| {
+                eprintln!("Failed to fetch the {caption_market} captions: {e}");
+                Vec::new()
+            })
+    };
+
+    // Same cutoff as cleanup: a day before it would be deleted again
+    let cutoff = (*keep_days > 0)
+        .then(|| chrono::Local::now().date_naive() - chrono::Duration::days(*keep_days as i64));
+
+    let source = settings.source(market);
+    let mut backfill = Backfill::default();
+    for image in &images {
+        let date = chrono::NaiveDate::parse_from_str(&image.date, "%Y%m%d").ok();
+        if date.zip(cutoff).is_some_and(|(date, cutoff)| date < cutoff) {
+            continue;
+        }
+        let mut info = ImageMetadata::from_bing(image, "");
+        if let Some(caption) = find_caption(&captions, image) {
+            info.set_caption(caption);
+        }
+        info.filename = naming.path_for(&info, &requested_size(resolution));
+        if source::existing_path(&info, wallpaper_dir).is_some() {
+            continue;
+        }
+        let downloaded = retry::retry(
+            policy,
+            || source::download(&source, &info, wallpaper_dir, &http::no_progress),
+            &on_retry,
+        ).await;
+        match downloaded {
+            Ok(path) => backfill.downloaded.push(path),
+            Err(e) => {
+                eprintln!("Failed to download {} ({}): {e}", info.display_date(), info.title);
+                backfill.failed.push((info.display_date(), e));
+            }
+        }
+    }
 
-    api_response
-        .images
+    Ok(backfill)
+}
+
+/// Downloads the image `market` featured `idx` days ago (0 = today), unless
+/// it is already stored, e.g. for a display that shows another market or day
+/// (see [`crate::outputs`]).
+///
+/// This is a single attempt; callers retry it with `settings.retry`.
+///
+/// # Returns
+/// * `Ok(String)` - Path of the stored image
+/// * `Err(Error)` - The archive request or the download failed
+pub async fn download_day(settings: &ArchiveSettings, market: &str, idx: u32) -> Result<String, Error> {
+    let image = fetch_bing_archive(&settings.net, &settings.host, market, idx, 1)
+        .await?
         .into_iter()
         .next()
-        .map(BingImage::from)
-        .ok_or_else(|| "No images in Bing response".to_string())
+        .ok_or_else(|| Error::InvalidResponse(format!("No {market} image from {idx} day(s) ago")))?;
+
+    let mut info = ImageMetadata::from_bing(&image, "");
+    add_caption(&settings.net, &settings.host, &settings.caption_market, &image, &mut info).await;
+    info.filename = settings.naming.path_for(&info, &requested_size(&settings.resolution));
+
+    let path = match source::existing_path(&info, &settings.wallpaper_dir) {
+        Some(path) => path,
+        None => {
+            source::download(&settings.source(market), &info, &settings.wallpaper_dir, &http::no_progress).await?
+        }
+    };
+    // Another market's copy of the same photo may have been reused
+    crate::metadata::add_market(&path, &image.market);
+    Ok(path)
+}
+
+/// The configured size, for the `{resolution}` placeholder (e.g., "UHD").
+///
+/// This is the size asked for, not necessarily the one downloaded: the name
+/// is needed before the download to find an existing copy, and a missing
+/// variant or a metered connection can give a smaller image. `Auto` is
+/// "auto" rather than the size picked for the current displays, so plugging
+/// in another monitor doesn't rename (and re-download) every image.
+fn requested_size(resolution: &Resolution) -> String {
+    match resolution {
+        Resolution::Auto => "auto".to_string(),
+        _ => size_candidates(resolution).swap_remove(0),
+    }
+}
+
+/// Returns the Bing size suffixes to try for a resolution, best first.
+///
+/// The list starts at the requested size and continues with every smaller
+/// size of the same orientation, so a missing variant falls back gracefully.
+/// `Auto` picks the largest size that suits the connected displays.
+fn size_candidates(resolution: &Resolution) -> Vec<String> {
+    let start = match resolution {
+        Resolution::Auto => auto_size(&crate::display::connected_displays()),
+        Resolution::Uhd => "UHD".to_string(),
+        Resolution::FullHd => "1920x1080".to_string(),
+        Resolution::Portrait => "1080x1920".to_string(),
+        Resolution::Custom(size) => size.clone(),
+    };
+
+    let portrait = crate::display::parse_mode(&start)
+        .map(|(w, h)| h > w)
+        .unwrap_or(false);
+    let sizes = if portrait { PORTRAIT_SIZES } else { LANDSCAPE_SIZES };
+
+    // Start from the requested size; custom sizes that Bing doesn't list
+    // fall through to every known size below them (by pixel count).
+    let area = |size: &str| crate::display::parse_mode(size).map(|(w, h)| w * h).unwrap_or(u32::MAX);
+    let mut candidates = vec![start.clone()];
+    candidates.extend(
+        sizes.iter()
+            .filter(|s| **s != start && area(s) < area(&start))
+            .map(|s| s.to_string()),
+    );
+    candidates
+}
+
+/// Picks the smallest Bing size that covers the largest connected display.
+///
+/// Falls back to 1920x1080 (Bing's default) when no displays are detected.
+fn auto_size(displays: &[crate::display::Display]) -> String {
+    let Some(largest) = displays.iter().max_by_key(|d| d.width * d.height) else {
+        return "1920x1080".to_string();
+    };
+
+    if largest.width > 1920 || largest.height > 1200 {
+        "UHD".to_string()
+    } else if largest.height > 1080 {
+        "1920x1200".to_string()
+    } else {
+        "1920x1080".to_string()
+    }
 }
 
 /// Downloads a Bing image to the local wallpaper directory.
 ///
-/// The image is saved with a date and market-based filename
-/// (e.g., "bing-en-US-2026-01-15.jpg"). If the file already exists,
-/// the download is skipped and the existing path is returned.
+/// The image is saved under the configured [filename template](crate::naming)
+/// (by default "bing-en-US-2026-01-15.jpg") through the shared
+/// [source pipeline](crate::source::download): existing files and copies of
+/// the same photo from other markets are reused, and the metadata is
+/// recorded in the index.
 ///
 /// # Arguments
 /// * `image` - Image metadata from [`fetch_bing_image_info`]
 /// * `wallpaper_dir` - Directory to save the image (created if it doesn't exist)
-/// * `market` - Market code to include in filename (allows different images per region)
+/// * `market` - Market code the image was fetched for
+/// * `resolution` - Preferred image size; smaller sizes are tried if Bing returns 404
+///   (see [`download_resolution`] for the size to use on a metered connection)
+/// * `naming` - How to name the file, usually [`FileNaming::from_config`]
+/// * `net` - Proxy, certificates and User-Agent for the download
 ///
 /// # Returns
 /// * `Ok(String)` - Absolute path to the downloaded (or existing) image file
-/// * `Err(String)` - Error message if directory creation, download, or save fails
+/// * `Err(Error)` - Error if directory creation, download, or save fails
 ///
 /// # Filename Format
-/// Images are saved as `bing-{market}-YYYY-MM-DD.jpg` where the date is from
-/// Bing's API response (the date the image was featured), not the local system date.
-pub async fn download_image(image: &BingImage, wallpaper_dir: &str, market: &str) -> Result<String, String> {
-    // Create wallpaper directory if needed
-    let dir = Path::new(wallpaper_dir);
-    std::fs::create_dir_all(dir)
-        .map_err(|e| format!("Failed to create wallpaper directory: {e}"))?;
-
-    // Generate filename based on market and Bing's image date (not local date)
-    // Bing returns date as YYYYMMDD, convert to YYYY-MM-DD for filename
-    let date = if image.date.len() == 8 {
-        format!("{}-{}-{}", &image.date[0..4], &image.date[4..6], &image.date[6..8])
+/// `{date}` in the template is from Bing's API response (the date the image
+/// was featured), not the local system date.
+pub async fn download_image(
+    image: &BingImage,
+    wallpaper_dir: &str,
+    market: &str,
+    resolution: &Resolution,
+    naming: &FileNaming,
+    net: &NetworkSettings,
+) -> Result<String, Error> {
+    // The image URLs are already absolute, so the host isn't needed here
+    let source = BingSource::new(DEFAULT_HOST, market, resolution.clone()).with_network_settings(net.clone());
+    let mut info = ImageMetadata::from_bing(image, "");
+    info.filename = naming.path_for(&info, &requested_size(resolution));
+    source::download(&source, &info, wallpaper_dir, &http::no_progress).await
+}
+
+/// The size to download given the configured `resolution` and the metered policy.
+///
+/// Under [`MeteredPolicy::Reduce`], a metered connection (`network`, as
+/// reported by NetworkManager) gets [`Resolution::reduced`]; otherwise
+/// `resolution` is used.
+pub fn download_resolution(resolution: &Resolution, metered: MeteredPolicy, network: &NetworkStatus) -> Resolution {
+    if metered == MeteredPolicy::Reduce && network.metered {
+        let reduced = resolution.reduced();
+        eprintln!("Metered connection: downloading {} instead of {}", reduced.label(), resolution.label());
+        reduced
     } else {
-        // Fallback to raw date if format is unexpected
-        image.date.clone()
+        resolution.clone()
+    }
+}
+
+/// Downloads an image to `dest`, trying each size variant in turn and falling back on 404.
+///
+/// The plain `url` (Bing's default size) is the last resort.
+async fn fetch_image_to_file(
+    net: &NetworkSettings,
+    urlbase: &str,
+    url: &str,
+    resolution: &Resolution,
+    dest: &Path,
+    progress: &Progress,
+) -> Result<(), Error> {
+    let mut urls: Vec<String> = if urlbase.is_empty() {
+        Vec::new()
+    } else {
+        size_candidates(resolution)
+            .iter()
+            .map(|size| format!("{}_{}.jpg", urlbase, size))
+            .collect()
     };
-    let filename = format!("bing-{}-{}.jpg", market, date);
-    let filepath = dir.join(&filename);
-    let filepath_str = filepath.to_string_lossy().to_string();
+    urls.push(url.to_string());
 
-    // Skip download if already exists (idempotent operation)
-    if filepath.exists() {
-        return Ok(filepath_str);
+    for url in &urls {
+        match http::download_to_file(net, url, dest, progress).await? {
+            DownloadStatus::Complete => return Ok(()),
+            DownloadStatus::NotFound => continue,
+        }
     }
+    Err(Error::Http { status: 404, message: "Image not available in any resolution".to_string() })
+}
 
-    // Download the image bytes with timeout
-    let client = create_client()?;
-    let response = client.get(&image.url)
-        .send()
-        .await
-        .map_err(|e| {
-            if e.is_timeout() {
-                "Download timed out - check your internet connection".to_string()
-            } else {
-                format!("Failed to download image: {e}")
-            }
-        })?;
+/// Bing's Image of the Day as a [`WallpaperSource`].
+pub struct BingSource {
+    /// Bing host to query (e.g., "https://www.bing.com")
+    host: String,
+    /// Regional market code (e.g., "en-US")
+    market: String,
+    /// Preferred image size
+    resolution: Resolution,
+    /// Market whose language the caption is shown in (empty for `market`'s own)
+    caption_market: String,
+    /// How the image is named in the wallpaper directory
+    naming: FileNaming,
+    /// What to do on a metered connection
+    metered: MeteredPolicy,
+    /// The network state when the source was made
+    network: NetworkStatus,
+    /// Proxy, certificates and User-Agent for requests
+    net: NetworkSettings,
+}
 
-    let bytes = response
-        .bytes()
-        .await
-        .map_err(|e| format!("Failed to read image data: {e}"))?;
+impl BingSource {
+    pub fn new(host: &str, market: &str, resolution: Resolution) -> Self {
+        Self {
+            host: host.to_string(),
+            market: market.to_string(),
+            resolution,
+            caption_market: String::new(),
+            naming: FileNaming::default(),
+            metered: MeteredPolicy::default(),
+            network: NetworkStatus::default(),
+            net: NetworkSettings::default(),
+        }
+    }
+
+    /// Also fetches the caption in `caption_market`'s language.
+    pub fn with_caption_market(mut self, caption_market: &str) -> Self {
+        self.caption_market = caption_market.to_string();
+        self
+    }
+
+    /// Names images by `naming` instead of the default `bing-{market}-{date}.jpg`.
+    pub fn with_naming(mut self, naming: FileNaming) -> Self {
+        self.naming = naming;
+        self
+    }
+
+    /// Applies the `metered` policy to downloads, given the `network` state
+    /// (see [`download_resolution`]).
+    pub fn with_network(mut self, metered: MeteredPolicy, network: NetworkStatus) -> Self {
+        self.metered = metered;
+        self.network = network;
+        self
+    }
+
+    /// Makes requests with `net` instead of the default settings.
+    pub fn with_network_settings(mut self, net: NetworkSettings) -> Self {
+        self.net = net;
+        self
+    }
+}
+
+impl WallpaperSource for BingSource {
+    fn fetch_info(&self) -> SourceFuture<'_, ImageMetadata> {
+        Box::pin(async move {
+            let image = fetch_bing_image_info(&self.net, &self.host, &self.market).await?;
+            let mut info = ImageMetadata::from_bing(&image, "");
+            // The caption comes first: the template may use the translated title
+            add_caption(&self.net, &self.host, &self.caption_market, &image, &mut info).await;
+            info.filename = self.naming.path_for(&info, &requested_size(&self.resolution));
+            Ok(info)
+        })
+    }
+
+    fn fetch_image<'a>(
+        &'a self,
+        info: &'a ImageMetadata,
+        dest: &'a Path,
+        progress: &'a Progress,
+    ) -> SourceFuture<'a, ()> {
+        Box::pin(async move {
+            let resolution = download_resolution(&self.resolution, self.metered, &self.network);
+            fetch_image_to_file(&self.net, &info.urlbase, &info.url, &resolution, dest, progress).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::Display;
+
+    #[test]
+    fn test_metered_sizes_are_smaller() {
+        assert_eq!(Resolution::Auto.reduced(), Resolution::FullHd);
+        assert_eq!(Resolution::Uhd.reduced(), Resolution::FullHd);
+        assert_eq!(Resolution::FullHd.reduced(), Resolution::Custom("1366x768".to_string()));
+        assert_eq!(Resolution::Portrait.reduced(), Resolution::Custom("768x1366".to_string()));
+        assert_eq!(Resolution::Custom("2560x1440".to_string()).reduced(), Resolution::FullHd);
+        assert_eq!(Resolution::Custom("1280x720".to_string()).reduced(), Resolution::Custom("1280x720".to_string()));
 
-    // Validate that we received an actual image (check magic bytes)
-    // JPEG starts with FF D8 FF, PNG starts with 89 50 4E 47
-    if bytes.len() < 4 {
-        return Err("Downloaded file is too small to be an image".to_string());
+        let metered = NetworkStatus { metered: true, ..NetworkStatus::default() };
+        assert_eq!(download_resolution(&Resolution::Uhd, MeteredPolicy::Reduce, &metered), Resolution::FullHd);
+        assert_eq!(download_resolution(&Resolution::Uhd, MeteredPolicy::Skip, &metered), Resolution::Uhd);
+        assert_eq!(download_resolution(&Resolution::Uhd, MeteredPolicy::Reduce, &NetworkStatus::default()), Resolution::Uhd);
     }
-    let is_jpeg = bytes[0] == 0xFF && bytes[1] == 0xD8 && bytes[2] == 0xFF;
-    let is_png = bytes[0] == 0x89 && bytes[1] == 0x50 && bytes[2] == 0x4E && bytes[3] == 0x47;
-    if !is_jpeg && !is_png {
-        return Err("Downloaded content is not a valid image (may be an error page)".to_string());
+
+    #[test]
+    fn test_size_candidates_fall_back_to_smaller_sizes() {
+        let candidates = size_candidates(&Resolution::Uhd);
+        assert_eq!(candidates[0], "UHD");
+        assert_eq!(candidates[1], "1920x1200");
+        assert!(candidates.contains(&"1366x768".to_string()));
+
+        let portrait = size_candidates(&Resolution::Portrait);
+        assert_eq!(portrait, vec!["1080x1920", "768x1366", "768x1280", "720x1280", "480x800"]);
+
+        let custom = size_candidates(&Resolution::Custom("1366x768".to_string()));
+        assert_eq!(custom[0], "1366x768");
+        assert!(!custom.contains(&"1920x1080".to_string()));
     }
 
-    // Save to disk
-    std::fs::write(&filepath, bytes)
-        .map_err(|e| format!("Failed to save image: {e}"))?;
+    #[test]
+    fn test_requested_size_names_the_configured_size() {
+        assert_eq!(requested_size(&Resolution::Uhd), "UHD");
+        assert_eq!(requested_size(&Resolution::Custom("1366x768".to_string())), "1366x768");
+        // Not whatever the connected displays resolve to
+        assert_eq!(requested_size(&Resolution::Auto), "auto");
+    }
+
+    #[test]
+    fn test_auto_size_matches_largest_display() {
+        let display = |width, height| Display { name: "DP-1".to_string(), width, height };
+        assert_eq!(auto_size(&[]), "1920x1080");
+        assert_eq!(auto_size(&[display(1920, 1080)]), "1920x1080");
+        assert_eq!(auto_size(&[display(1920, 1080), display(3840, 2160)]), "UHD");
+        assert_eq!(auto_size(&[display(1920, 1200)]), "1920x1200");
+    }
 
-    Ok(filepath_str)
+    #[test]
+    fn test_find_caption_matches_the_same_photo() {
+        let image = |hsh: &str, urlbase: &str, title: &str| BingImage {
+            url: String::new(),
+            urlbase: urlbase.to_string(),
+            copyright: String::new(),
+            copyright_link: String::new(),
+            title: title.to_string(),
+            date: "20260205".to_string(),
+            end_date: String::new(),
+            hsh: hsh.to_string(),
+            market: "de-DE".to_string(),
+        };
+        let captions = vec![
+            image("111", "https://www.bing.com/th?id=OHR.Fox_DE-DE123", "Fuchs"),
+            image("222", "https://www.bing.com/th?id=OHR.Owl_DE-DE456", "Eule"),
+        ];
+
+        let by_hash = image("222", "https://www.bing.com/th?id=OHR.Other_EN-US1", "Owl");
+        assert_eq!(find_caption(&captions, &by_hash).unwrap().title, "Eule");
+
+        let by_id = image("", "https://www.bing.com/th?id=OHR.Fox_EN-US789", "Fox");
+        assert_eq!(find_caption(&captions, &by_id).unwrap().title, "Fuchs");
+
+        let missing = image("333", "https://www.bing.com/th?id=OHR.Bear_EN-US1", "Bear");
+        assert!(find_caption(&captions, &missing).is_none());
+        assert_eq!(photo_id("/th?id=OHR.Fox_EN-US789"), Some("OHR.Fox"));
+        assert_eq!(photo_id("/th?id=nounderscore"), None);
+        assert_eq!(urlbase_market("/th?id=OHR.Fox_EN-US789"), Some("EN-US"));
+        assert_eq!(urlbase_market("/th?id=OHR.Fox_ROW123"), Some("ROW"));
+        assert_eq!(urlbase_market("/th?id=nounderscore"), None);
+    }
+
+    #[test]
+    fn test_normalize_host() {
+        assert_eq!(normalize_host(""), DEFAULT_HOST);
+        assert_eq!(normalize_host("cn.bing.com/"), "https://cn.bing.com");
+        assert_eq!(normalize_host("http://127.0.0.1:8080"), "http://127.0.0.1:8080");
+    }
+
+    #[tokio::test]
+    async fn test_custom_host_is_used_for_api_and_images() {
+        let body = r#"{"images":[{"url":"/th?id=OHR.Test_1920x1080.jpg","urlbase":"/th?id=OHR.Test",
+            "copyright":"Test (© Someone)","copyrightlink":"/search?q=test","title":"Test",
+            "startdate":"20260205","enddate":"20260206","hsh":"abc"}]}"#;
+        let host = crate::test_http::serve(vec![(
+            "/HPImageArchive.aspx?format=js&idx=0&n=1&mkt=en-US",
+            "application/json",
+            body.as_bytes().to_vec(),
+        )]).await;
+
+        let image = fetch_bing_image_info(&NetworkSettings::default(), &host, "en-US").await.unwrap();
+        assert_eq!(image.urlbase, format!("{host}/th?id=OHR.Test"));
+        assert_eq!(image.copyright_link, format!("{host}/search?q=test"));
+        assert_eq!(image.market, "en-US");
+
+        // A custom host that fails is not retried elsewhere
+        assert!(fetch_bing_image_info(&NetworkSettings::default(), &host, "de-DE").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_probe_rejects_markets_bing_falls_back_for() {
+        let answer = |suffix: &str| {
+            format!(r#"{{"images":[{{"url":"/th?id=OHR.Fox_{suffix}_1920x1080.jpg","urlbase":"/th?id=OHR.Fox_{suffix}",
+                "copyright":"","title":"Fox","startdate":"20260205","hsh":"abc"}}]}}"#).into_bytes()
+        };
+        let host = crate::test_http::serve(vec![
+            ("/HPImageArchive.aspx?format=js&idx=0&n=1&mkt=es-CL", "application/json", answer("ES-CL123")),
+            ("/HPImageArchive.aspx?format=js&idx=0&n=1&mkt=zz-ZZ", "application/json", answer("ROW123")),
+        ]).await;
+
+        assert!(probe_market(&NetworkSettings::default(), &host, "es-CL").await.is_ok());
+        assert!(matches!(probe_market(&NetworkSettings::default(), &host, "zz-ZZ").await, Err(Error::Config(m)) if m.contains("ROW")));
+    }
+
+    #[tokio::test]
+    async fn test_backfill_skips_failed_days() {
+        // Different sizes, so the two days aren't deduplicated by content
+        let jpeg = |width, height| {
+            let mut bytes = Vec::new();
+            image::DynamicImage::new_rgb8(width, height)
+                .write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageFormat::Jpeg)
+                .unwrap();
+            bytes
+        };
+        // Three days; the middle one has no downloadable variant
+        let body = r#"{"images":[
+            {"url":"/th?id=OHR.Fox_1920x1080.jpg","urlbase":"/th?id=OHR.Fox","copyright":"","title":"Fox","startdate":"20260207","hsh":"1"},
+            {"url":"/th?id=OHR.Gone_1920x1080.jpg","urlbase":"/th?id=OHR.Gone","copyright":"","title":"Gone","startdate":"20260206","hsh":"2"},
+            {"url":"/th?id=OHR.Owl_1920x1080.jpg","urlbase":"/th?id=OHR.Owl","copyright":"","title":"Owl","startdate":"20260205","hsh":"3"}]}"#;
+        let host = crate::test_http::serve(vec![
+            ("/HPImageArchive.aspx?format=js&idx=0&n=8&mkt=en-US", "application/json", body.as_bytes().to_vec()),
+            ("/th?id=OHR.Fox_1920x1080.jpg", "image/jpeg", jpeg(640, 480)),
+            ("/th?id=OHR.Owl_1920x1080.jpg", "image/jpeg", jpeg(800, 600)),
+        ]).await;
+        let dir = std::env::temp_dir().join(format!("bing-wallpaper-backfill-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let settings = ArchiveSettings {
+            host,
+            caption_market: String::new(),
+            naming: FileNaming::default(),
+            wallpaper_dir: dir.to_string_lossy().to_string(),
+            resolution: Resolution::FullHd,
+            retry: RetryPolicy::default(),
+            keep_days: 0,
+            metered: MeteredPolicy::default(),
+            network: NetworkStatus::default(),
+            net: NetworkSettings::default(),
+        };
+
+        // The missing day is a 404, which isn't retried
+        let backfill = backfill_archive(&settings, "en-US", |r| panic!("retried: {}", r.message())).await.unwrap();
+        assert_eq!(backfill.downloaded, vec![
+            dir.join("bing-en-US-2026-02-07.jpg").to_string_lossy().to_string(),
+            dir.join("bing-en-US-2026-02-05.jpg").to_string_lossy().to_string(),
+        ]);
+        assert_eq!(backfill.failed.len(), 1);
+        assert_eq!(backfill.failed[0].0, "2026-02-06");
+        assert_eq!(backfill.failed[0].1.status(), Some(404));
+        assert!(backfill.error_summary().unwrap().contains("2026-02-06"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_backfill_skips_days_cleanup_would_delete() {
+        let mut bytes = Vec::new();
+        image::DynamicImage::new_rgb8(640, 480)
+            .write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageFormat::Jpeg)
+            .unwrap();
+        // Today and five days ago; only today's image is served
+        let day = |days_ago| (chrono::Local::now().date_naive() - chrono::Duration::days(days_ago)).format("%Y%m%d");
+        let body = format!(r#"{{"images":[
+            {{"url":"/th?id=OHR.New_1920x1080.jpg","urlbase":"/th?id=OHR.New","copyright":"","title":"New","startdate":"{}","hsh":"1"}},
+            {{"url":"/th?id=OHR.Old_1920x1080.jpg","urlbase":"/th?id=OHR.Old","copyright":"","title":"Old","startdate":"{}","hsh":"2"}}]}}"#,
+            day(0), day(5));
+        let host = crate::test_http::serve(vec![
+            ("/HPImageArchive.aspx?format=js&idx=0&n=8&mkt=en-US", "application/json", body.into_bytes()),
+            ("/th?id=OHR.New_1920x1080.jpg", "image/jpeg", bytes),
+        ]).await;
+        let dir = std::env::temp_dir().join(format!("bing-wallpaper-backfill-keep-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let settings = ArchiveSettings {
+            host,
+            caption_market: String::new(),
+            naming: FileNaming::default(),
+            wallpaper_dir: dir.to_string_lossy().to_string(),
+            resolution: Resolution::FullHd,
+            retry: RetryPolicy::default(),
+            keep_days: 3,
+            metered: MeteredPolicy::default(),
+            network: NetworkStatus::default(),
+            net: NetworkSettings::default(),
+        };
+
+        // The old day isn't even tried, so it doesn't fail either
+        let backfill = backfill_archive(&settings, "en-US", |r| panic!("retried: {}", r.message())).await.unwrap();
+        assert_eq!(backfill.downloaded.len(), 1);
+        assert!(backfill.downloaded[0].ends_with(&format!("bing-en-US-{}.jpg", chrono::Local::now().format("%Y-%m-%d"))));
+        assert!(backfill.failed.is_empty());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
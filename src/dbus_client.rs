@@ -41,9 +41,16 @@ trait WallpaperService {
     /// Fetch today's wallpaper, optionally apply it
     async fn fetch_wallpaper(&self, apply: bool) -> zbus::Result<WallpaperInfo>;
 
+    /// Download any of the last 8 days missing from the wallpaper directory
+    async fn backfill_archive(&self) -> zbus::Result<Vec<WallpaperInfo>>;
+
     /// Apply a specific wallpaper by path
     async fn apply_wallpaper(&self, path: &str) -> zbus::Result<()>;
 
+    /// Go back to the wallpaper shown before the last change
+    /// Returns the image now shown, or an empty string if it isn't an image
+    async fn revert_wallpaper(&self) -> zbus::Result<String>;
+
     /// Get current configuration as JSON
     async fn get_config(&self) -> zbus::Result<String>;
 
@@ -53,6 +60,9 @@ trait WallpaperService {
     /// Set the Bing regional market
     async fn set_market(&self, market: &str) -> zbus::Result<()>;
 
+    /// Check a market code against the Bing API and save it as a custom market
+    async fn add_custom_market(&self, market: &str) -> zbus::Result<()>;
+
     /// Get the wallpaper directory path
     async fn get_wallpaper_dir(&self) -> zbus::Result<String>;
 
@@ -128,11 +138,23 @@ impl WallpaperClient {
         self.proxy.fetch_wallpaper(apply).await
     }
 
+    /// Download any of the last 8 days missing from the wallpaper directory
+    ///
+    /// Returns the newly downloaded wallpapers
+    pub async fn backfill_archive(&self) -> zbus::Result<Vec<WallpaperInfo>> {
+        self.proxy.backfill_archive().await
+    }
+
     /// Apply a specific wallpaper by path
     pub async fn apply_wallpaper(&self, path: &str) -> zbus::Result<()> {
         self.proxy.apply_wallpaper(path).await
     }
 
+    /// Go back to the wallpaper shown before the last change
+    pub async fn revert_wallpaper(&self) -> zbus::Result<String> {
+        self.proxy.revert_wallpaper().await
+    }
+
     /// Get current configuration as JSON
     pub async fn get_config(&self) -> zbus::Result<String> {
         self.proxy.get_config().await
@@ -143,11 +165,16 @@ impl WallpaperClient {
         self.proxy.get_market().await
     }
 
-    /// Set the Bing regional market
+    /// Set the Bing regional market (rejected unless known or a custom market)
     pub async fn set_market(&self, market: &str) -> zbus::Result<()> {
         self.proxy.set_market(market).await
     }
 
+    /// Check a market code against the Bing API and save it as a custom market
+    pub async fn add_custom_market(&self, market: &str) -> zbus::Result<()> {
+        self.proxy.add_custom_market(market).await
+    }
+
     /// Get the wallpaper directory path
     pub async fn get_wallpaper_dir(&self) -> zbus::Result<String> {
         self.proxy.get_wallpaper_dir().await
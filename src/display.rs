@@ -0,0 +1,73 @@
+//! # Display Detection
+//!
+//! Finds the connected displays and their native resolutions so the app can
+//! pick a matching Bing image size.
+//!
+//! ## How It Works
+//! The kernel's DRM subsystem exposes every connector under `/sys/class/drm/`
+//! (e.g., `card1-DP-1`). Each connector has a `status` file (`connected` or
+//! `disconnected`) and a `modes` file listing supported modes, preferred first:
+//! ```text
+//! 3840x2160
+//! 2560x1440
+//! 1920x1080
+//! ```
+//! This works the same natively and inside Flatpak, and needs no compositor API.
+
+use std::path::Path;
+
+/// Root of the DRM connector tree in sysfs.
+const DRM_SYSFS_DIR: &str = "/sys/class/drm";
+
+/// A connected display and its preferred (native) mode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Display {
+    /// Connector name without the card prefix (e.g., "DP-1", "eDP-1")
+    pub name: String,
+    /// Native width in pixels
+    pub width: u32,
+    /// Native height in pixels
+    pub height: u32,
+}
+
+/// Lists all connected displays with their preferred resolution.
+///
+/// Returns an empty list if sysfs is unavailable or no display reports a mode.
+pub fn connected_displays() -> Vec<Display> {
+    let Ok(entries) = std::fs::read_dir(DRM_SYSFS_DIR) else {
+        return Vec::new();
+    };
+
+    let mut displays: Vec<Display> = entries
+        .flatten()
+        .filter_map(|entry| read_connector(&entry.path()))
+        .collect();
+
+    displays.sort_by(|a, b| a.name.cmp(&b.name));
+    displays
+}
+
+/// Reads a single DRM connector directory, returning None if it is not a
+/// connected display (e.g., the `card1` device node itself or an empty port).
+fn read_connector(path: &Path) -> Option<Display> {
+    let dir_name = path.file_name()?.to_str()?;
+    // Connector directories look like "card1-DP-1"; skip "card1", "renderD128", etc.
+    let (_, name) = dir_name.split_once('-')?;
+
+    let status = std::fs::read_to_string(path.join("status")).ok()?;
+    if status.trim() != "connected" {
+        return None;
+    }
+
+    let modes = std::fs::read_to_string(path.join("modes")).ok()?;
+    let (width, height) = parse_mode(modes.lines().next()?)?;
+
+    Some(Display { name: name.to_string(), width, height })
+}
+
+/// Parses a mode string like "1920x1080" (or "1920x1080i") into width and height.
+pub fn parse_mode(mode: &str) -> Option<(u32, u32)> {
+    let (w, h) = mode.trim().split_once('x')?;
+    let h = h.trim_end_matches(|c: char| !c.is_ascii_digit());
+    Some((w.parse().ok()?, h.parse().ok()?))
+}
@@ -0,0 +1,165 @@
+//! # Errors
+//!
+//! The error type for fetching, storing and applying wallpapers.
+//!
+//! Each variant says *what kind* of thing went wrong, so callers can decide
+//! whether trying again makes sense: a timeout or an HTTP 503 usually fixes
+//! itself, while a 404, a malformed response or a full disk does not. The
+//! human-readable message is kept as-is and is what [`Display`] prints, so
+//! the UI shows the same text as before.
+//!
+//! | Variant | Example | Transient |
+//! |---------|---------|-----------|
+//! | `Timeout` | Request timed out | yes |
+//! | `Network` | DNS failure, connection refused, download cut short | yes |
+//! | `Http` | HTTP 404, HTTP 503 | 408, 429 and 5xx only |
+//! | `InvalidResponse` | Malformed JSON, no images, corrupt image | no |
+//! | `Io` | Disk full, permission denied | no |
+//! | `Config` | Bad proxy URL, no feed URL configured | no |
+//!
+//! [`Display`]: std::fmt::Display
+
+use std::fmt;
+
+/// What went wrong while fetching, storing or applying a wallpaper.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The request timed out
+    Timeout(String),
+    /// The server couldn't be reached, or the connection broke mid-transfer
+    Network(String),
+    /// The server answered with an HTTP error status
+    Http {
+        /// HTTP status code (e.g., 503)
+        status: u16,
+        /// Human-readable message including the status
+        message: String,
+    },
+    /// The response couldn't be used (malformed JSON/XML, no images, invalid image)
+    InvalidResponse(String),
+    /// A local file couldn't be read or written
+    Io(String),
+    /// The configuration can't work (bad URL, missing feed, unreadable CA bundle)
+    Config(String),
+}
+
+impl Error {
+    /// Classifies a failed `reqwest` call, keeping `message` as the text.
+    pub fn request(e: &reqwest::Error, message: String) -> Self {
+        if e.is_timeout() {
+            Error::Timeout(message)
+        } else if let Some(status) = e.status() {
+            Error::Http { status: status.as_u16(), message }
+        } else if e.is_builder() {
+            Error::Config(message)
+        } else if e.is_decode() {
+            Error::InvalidResponse(message)
+        } else {
+            // Connect, body and other transport errors
+            Error::Network(message)
+        }
+    }
+
+    /// An HTTP error status, reported as "{context}: HTTP {status}".
+    pub fn http(context: &str, status: reqwest::StatusCode) -> Self {
+        Error::Http { status: status.as_u16(), message: format!("{context}: HTTP {status}") }
+    }
+
+    /// Whether trying again later might succeed.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::Timeout(_) | Error::Network(_) => true,
+            Error::Http { status, .. } => matches!(status, 408 | 429 | 500..=599),
+            Error::InvalidResponse(_) | Error::Io(_) | Error::Config(_) => false,
+        }
+    }
+
+    /// Whether the server couldn't be reached at all (as opposed to answering
+    /// with an error or an unusable response).
+    pub fn is_unreachable(&self) -> bool {
+        matches!(self, Error::Timeout(_) | Error::Network(_))
+    }
+
+    /// The HTTP status code, if the server answered with an error status.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Error::Http { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+
+    /// The human-readable message.
+    pub fn message(&self) -> &str {
+        match self {
+            Error::Timeout(m)
+            | Error::Network(m)
+            | Error::InvalidResponse(m)
+            | Error::Io(m)
+            | Error::Config(m) => m,
+            Error::Http { message, .. } => message,
+        }
+    }
+
+    /// Prefixes the message with `context` ("{context}: {message}"), keeping the kind.
+    pub fn context(self, context: &str) -> Self {
+        let wrap = |m: String| format!("{context}: {m}");
+        match self {
+            Error::Timeout(m) => Error::Timeout(wrap(m)),
+            Error::Network(m) => Error::Network(wrap(m)),
+            Error::Http { status, message } => Error::Http { status, message: wrap(message) },
+            Error::InvalidResponse(m) => Error::InvalidResponse(wrap(m)),
+            Error::Io(m) => Error::Io(wrap(m)),
+            Error::Config(m) => Error::Config(wrap(m)),
+        }
+    }
+
+    /// One-line status for the UI; transient errors are marked as worth another try.
+    pub fn status_message(&self) -> String {
+        if self.is_transient() {
+            format!("Temporary error, try again later: {self}")
+        } else {
+            format!("Error: {self}")
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// D-Bus callers get the closest standard error name, with the same message.
+impl From<Error> for zbus::fdo::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Timeout(m) => zbus::fdo::Error::TimedOut(m),
+            Error::Network(m) => zbus::fdo::Error::NoNetwork(m),
+            Error::Io(m) => zbus::fdo::Error::IOError(m),
+            e => zbus::fdo::Error::Failed(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transient_classification() {
+        let http = |status| Error::Http { status, message: String::new() };
+        assert!(Error::Timeout(String::new()).is_transient());
+        assert!(Error::Network(String::new()).is_transient());
+        assert!(http(503).is_transient());
+        assert!(http(429).is_transient());
+        assert!(!http(404).is_transient());
+        assert!(!Error::InvalidResponse(String::new()).is_transient());
+        assert!(!Error::Io(String::new()).is_transient());
+
+        let e = http(502).context("Failed to fetch");
+        assert_eq!(e.status(), Some(502));
+        assert_eq!(e.to_string(), "Failed to fetch: ");
+    }
+}
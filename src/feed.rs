@@ -0,0 +1,348 @@
+//! # Feed Source
+//!
+//! A [`WallpaperSource`] that follows a custom photo-of-the-day feed, so any
+//! site publishing a daily image can drive the same timer, history and apply
+//! machinery as Bing.
+//!
+//! ## Supported Feeds
+//! - **RSS 2.0** - the first `<item>` with an image `<enclosure>` or
+//!   `<media:content>` element
+//! - **Atom** - the first `<entry>` with a `<link rel="enclosure">` to an image
+//! - **JSON** - any document, with a JSONPath-style selector pointing at the
+//!   image URL (and optionally another one for the title)
+//!
+//! The format is detected from the response body: anything that parses as
+//! JSON is treated as JSON, everything else as XML.
+//!
+//! ## JSON Selectors
+//! Selectors use a small subset of JSONPath: an optional leading `$`, object
+//! keys separated by dots and array indices in brackets:
+//! ```text
+//! $.images[0].url
+//! data.photos[2]["full-size"]
+//! ```
+//!
+//! ## Storage
+//! The image is saved as `feed-YYYY-MM-DD.{ext}` (extension from the image
+//! URL, `.jpg` if it has none), so it appears in history like a Bing image.
+//! Relative image URLs are resolved against the feed URL.
+
+use serde_json::Value;
+use std::path::Path;
+
+use crate::error::Error;
+use crate::http::{self, DownloadStatus, NetworkSettings, Progress};
+use crate::metadata::ImageMetadata;
+use crate::source::{SourceFuture, WallpaperSource};
+
+/// Follows a user-configured RSS, Atom or JSON feed.
+pub struct FeedSource {
+    /// URL of the feed document
+    url: String,
+    /// JSON selector for the image URL (only used for JSON feeds)
+    image_selector: String,
+    /// JSON selector for the title (only used for JSON feeds, may be empty)
+    title_selector: String,
+    /// Proxy, certificates and User-Agent for requests
+    net: NetworkSettings,
+}
+
+/// The image picked from a feed, before URL resolution.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct FeedItem {
+    /// Image URL as written in the feed (may be relative)
+    image_url: String,
+    /// Item title (empty if the feed has none)
+    title: String,
+    /// Link to the item's page (empty if none)
+    link: String,
+}
+
+impl FeedSource {
+    pub fn new(url: &str, image_selector: &str, title_selector: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            image_selector: image_selector.to_string(),
+            title_selector: title_selector.to_string(),
+            net: NetworkSettings::default(),
+        }
+    }
+
+    /// Makes requests with `net` instead of the default settings.
+    pub fn with_network_settings(mut self, net: NetworkSettings) -> Self {
+        self.net = net;
+        self
+    }
+
+    /// Picks today's image out of a downloaded feed document.
+    fn parse(&self, body: &str) -> Result<FeedItem, Error> {
+        match serde_json::from_str::<Value>(body) {
+            Ok(json) => parse_json(&json, &self.image_selector, &self.title_selector),
+            Err(_) => parse_xml(body),
+        }
+    }
+
+    /// Resolves a possibly relative URL from the feed against the feed URL.
+    fn resolve(&self, url: &str) -> Result<String, Error> {
+        let base = reqwest::Url::parse(&self.url)
+            .map_err(|e| Error::Config(format!("Invalid feed URL {}: {e}", self.url)))?;
+        base.join(url)
+            .map(|u| u.to_string())
+            .map_err(|e| Error::InvalidResponse(format!("Invalid image URL {url}: {e}")))
+    }
+}
+
+impl WallpaperSource for FeedSource {
+    fn fetch_info(&self) -> SourceFuture<'_, ImageMetadata> {
+        Box::pin(async move {
+            if self.url.is_empty() {
+                return Err(Error::Config("No feed URL configured".to_string()));
+            }
+
+            let client = http::client(&self.net)?;
+            let response = client.get(&self.url)
+                .send()
+                .await
+                .map_err(|e| Error::request(&e, format!("Failed to fetch feed: {e}")))?;
+            if !response.status().is_success() {
+                return Err(Error::http("Failed to fetch feed", response.status()));
+            }
+            let body = response.text()
+                .await
+                .map_err(|e| Error::request(&e, format!("Failed to read feed: {e}")))?;
+
+            let item = self.parse(&body)?;
+            let image_url = self.resolve(&item.image_url)?;
+            let link = if item.link.is_empty() { String::new() } else { self.resolve(&item.link)? };
+
+            let today = chrono::Local::now().date_naive();
+            Ok(ImageMetadata {
+                filename: format!("feed-{}.{}", today.format("%Y-%m-%d"), image_extension(&image_url)),
+                title: item.title,
+                copyright_link: link,
+                start_date: today.format("%Y%m%d").to_string(),
+                url: image_url,
+                ..Default::default()
+            })
+        })
+    }
+
+    fn fetch_image<'a>(
+        &'a self,
+        info: &'a ImageMetadata,
+        dest: &'a Path,
+        progress: &'a Progress,
+    ) -> SourceFuture<'a, ()> {
+        Box::pin(async move {
+            match http::download_to_file(&self.net, &info.url, dest, progress).await? {
+                DownloadStatus::Complete => Ok(()),
+                DownloadStatus::NotFound => Err(Error::Http {
+                    status: 404,
+                    message: format!("Feed image not found: {}", info.url),
+                }),
+            }
+        })
+    }
+}
+
+/// Extension to save an image under, taken from its URL path.
+fn image_extension(url: &str) -> &'static str {
+    let path = url.split(['?', '#']).next().unwrap_or("").to_lowercase();
+    if path.ends_with(".png") {
+        "png"
+    } else if path.ends_with(".jpeg") {
+        "jpeg"
+    } else {
+        "jpg"
+    }
+}
+
+/// Picks the image from a JSON feed using the configured selectors.
+fn parse_json(json: &Value, image_selector: &str, title_selector: &str) -> Result<FeedItem, Error> {
+    if image_selector.is_empty() {
+        return Err(Error::Config("JSON feeds need an image selector (e.g. $.images[0].url)".to_string()));
+    }
+
+    let image_url = select(json, image_selector)?
+        .as_str()
+        .ok_or_else(|| Error::InvalidResponse(format!("Selector {image_selector} does not point at a string")))?
+        .to_string();
+
+    // A missing title isn't worth failing the fetch over
+    let title = if title_selector.is_empty() {
+        String::new()
+    } else {
+        select(json, title_selector)
+            .ok()
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string()
+    };
+
+    Ok(FeedItem { image_url, title, link: String::new() })
+}
+
+/// One step of a JSON selector.
+#[derive(Debug, PartialEq)]
+enum Segment {
+    /// Object member, from `.key` or `["key"]`
+    Key(String),
+    /// Array element, from `[n]`
+    Index(usize),
+}
+
+/// Splits a selector like `$.images[0]["url"]` into its segments.
+fn parse_selector(selector: &str) -> Result<Vec<Segment>, Error> {
+    let invalid = || Error::Config(format!("Invalid selector: {selector}"));
+    let rest = selector.trim();
+    let mut rest = rest.strip_prefix('$').unwrap_or(rest);
+    let mut segments = Vec::new();
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('[') {
+            let (inner, after) = after.split_once(']').ok_or_else(invalid)?;
+            let quoted = inner
+                .strip_prefix('"').and_then(|s| s.strip_suffix('"'))
+                .or_else(|| inner.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')));
+            segments.push(match quoted {
+                Some(key) => Segment::Key(key.to_string()),
+                None => Segment::Index(inner.trim().parse().map_err(|_| invalid())?),
+            });
+            rest = after;
+        } else {
+            // Leading dot is optional so "images[0].url" works too
+            let after = rest.strip_prefix('.').unwrap_or(rest);
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            if end == 0 {
+                return Err(invalid());
+            }
+            segments.push(Segment::Key(after[..end].to_string()));
+            rest = &after[end..];
+        }
+    }
+
+    Ok(segments)
+}
+
+/// Follows a selector through a JSON document.
+fn select<'a>(json: &'a Value, selector: &str) -> Result<&'a Value, Error> {
+    parse_selector(selector)?
+        .iter()
+        .try_fold(json, |value, segment| {
+            match segment {
+                Segment::Key(key) => value.get(key.as_str()),
+                Segment::Index(i) => value.get(*i),
+            }
+        })
+        .ok_or_else(|| Error::InvalidResponse(format!("Selector {selector} matched nothing in the feed")))
+}
+
+/// Picks the first item with an image from an RSS or Atom feed.
+fn parse_xml(body: &str) -> Result<FeedItem, Error> {
+    let doc = roxmltree::Document::parse(body)
+        .map_err(|e| Error::InvalidResponse(format!("Feed is neither JSON nor valid XML: {e}")))?;
+
+    doc.descendants()
+        .filter(|n| n.has_tag_name("item") || n.has_tag_name("entry"))
+        .find_map(|item| {
+            let image_url = item_image(item)?;
+            let child_text = |name: &str| {
+                item.children()
+                    .find(|c| c.has_tag_name(name))
+                    .and_then(|c| c.text())
+                    .map(|t| t.trim().to_string())
+                    .unwrap_or_default()
+            };
+
+            // RSS puts the page link in <link>text</link>, Atom in <link href="...">
+            let link = item.children()
+                .find(|c| c.has_tag_name("link") && matches!(c.attribute("rel"), None | Some("alternate")))
+                .and_then(|c| c.attribute("href").map(str::to_string).or_else(|| c.text().map(|t| t.trim().to_string())))
+                .unwrap_or_default();
+
+            Some(FeedItem { image_url, title: child_text("title"), link })
+        })
+        .ok_or_else(|| Error::InvalidResponse("Feed has no items with an image".to_string()))
+}
+
+/// Finds an item's image: an RSS `<enclosure>` or `<media:content>`, or an
+/// Atom `<link rel="enclosure">`. Entries with a non-image type are skipped.
+fn item_image(item: roxmltree::Node) -> Option<String> {
+    let is_image = |node: &roxmltree::Node| {
+        node.attribute("type").map(|t| t.starts_with("image/")).unwrap_or(true)
+            && node.attribute("medium").map(|m| m == "image").unwrap_or(true)
+    };
+
+    item.children()
+        .filter(|c| c.is_element() && is_image(c))
+        .find_map(|c| match c.tag_name().name() {
+            "enclosure" | "content" => c.attribute("url"),
+            "link" if c.attribute("rel") == Some("enclosure") => c.attribute("href"),
+            _ => None,
+        })
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_http::serve;
+
+    #[test]
+    fn test_parse_selector() {
+        assert_eq!(
+            parse_selector("$.images[0][\"full-size\"]").unwrap(),
+            vec![Segment::Key("images".into()), Segment::Index(0), Segment::Key("full-size".into())],
+        );
+        assert_eq!(parse_selector("data.url").unwrap(), vec![Segment::Key("data".into()), Segment::Key("url".into())]);
+        assert!(parse_selector("$.images[x]").is_err());
+        assert!(parse_selector("$..url").is_err());
+    }
+
+    #[test]
+    fn test_parse_feeds() {
+        let rss = r#"<rss version="2.0"><channel>
+            <item><title>No image</title><enclosure url="a.mp3" type="audio/mpeg"/></item>
+            <item><title>Harbour</title><link>https://example.com/p/1</link>
+                <enclosure url="/img/harbour.jpg" type="image/jpeg"/></item>
+        </channel></rss>"#;
+        assert_eq!(parse_xml(rss).unwrap(), FeedItem {
+            image_url: "/img/harbour.jpg".into(),
+            title: "Harbour".into(),
+            link: "https://example.com/p/1".into(),
+        });
+
+        let atom = r#"<feed xmlns="http://www.w3.org/2005/Atom"><entry>
+            <title>Peak</title><link href="https://example.com/peak"/>
+            <link rel="enclosure" type="image/png" href="https://example.com/peak.png"/>
+        </entry></feed>"#;
+        assert_eq!(parse_xml(atom).unwrap().image_url, "https://example.com/peak.png");
+
+        let json: Value = serde_json::from_str(r#"{"photos":[{"src":"p.jpg","name":"Dunes"}]}"#).unwrap();
+        let item = parse_json(&json, "$.photos[0].src", "$.photos[0].name").unwrap();
+        assert_eq!((item.image_url.as_str(), item.title.as_str()), ("p.jpg", "Dunes"));
+        assert!(parse_json(&json, "$.photos[1].src", "").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_from_local_feed() {
+        let rss = r#"<rss><channel><item><title>Harbour</title>
+            <enclosure url="img/harbour.jpg" type="image/jpeg"/></item></channel></rss>"#;
+        let jpeg = vec![0xFF, 0xD8, 0xFF, 0xE0, 0, 0];
+        let base = serve(vec![
+            ("/feed.xml", "application/rss+xml", rss.as_bytes().to_vec()),
+            ("/img/harbour.jpg", "image/jpeg", jpeg.clone()),
+        ]).await;
+
+        let source = FeedSource::new(&format!("{base}/feed.xml"), "", "");
+        let info = source.fetch_info().await.unwrap();
+        assert_eq!(info.title, "Harbour");
+        assert_eq!(info.url, format!("{base}/img/harbour.jpg"));
+        assert!(info.filename.starts_with("feed-") && info.filename.ends_with(".jpg"));
+        let dest = std::env::temp_dir().join(format!("feed-test-{}.part", std::process::id()));
+        let _ = std::fs::remove_file(&dest);
+        source.fetch_image(&info, &dest, &http::no_progress).await.unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), jpeg);
+        let _ = std::fs::remove_file(&dest);
+    }
+}
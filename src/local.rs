@@ -0,0 +1,121 @@
+//! # Local Folder Source
+//!
+//! A [`WallpaperSource`] that picks the daily wallpaper from a user-chosen
+//! folder instead of Bing, so the scheduler, history and apply machinery
+//! also work with a personal photo collection.
+//!
+//! ## Selection
+//! The folder's images (`.jpg`, `.jpeg`, `.png`, not recursive) are sorted by
+//! name and the app steps through them one per day, wrapping around at the
+//! end. The same day always yields the same image.
+//!
+//! ## Storage
+//! The picked image is copied into the wallpaper directory as
+//! `local-YYYY-MM-DD.{ext}`, so it appears in history like any Bing image.
+
+use std::path::{Path, PathBuf};
+
+use chrono::Datelike;
+
+use crate::error::Error;
+use crate::http::Progress;
+use crate::metadata::ImageMetadata;
+use crate::source::{SourceFuture, WallpaperSource};
+
+/// Picks wallpapers from a folder of the user's own images.
+pub struct LocalFolderSource {
+    /// Folder to pick images from
+    folder: PathBuf,
+}
+
+impl LocalFolderSource {
+    pub fn new(folder: &str) -> Self {
+        Self { folder: PathBuf::from(folder) }
+    }
+
+    /// Lists the images in the folder, sorted by filename.
+    fn list_images(&self) -> Result<Vec<PathBuf>, Error> {
+        let entries = std::fs::read_dir(&self.folder)
+            .map_err(|e| Error::Io(format!("Failed to read folder {}: {e}", self.folder.display())))?;
+
+        let mut images: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension()
+                    .and_then(|ext| ext.to_str())
+                    .map(|ext| matches!(ext.to_lowercase().as_str(), "jpg" | "jpeg" | "png"))
+                    .unwrap_or(false)
+            })
+            .collect();
+
+        images.sort();
+        Ok(images)
+    }
+}
+
+/// Picks the image for a given day number, stepping through the list one per day.
+fn pick_for_day(images: &[PathBuf], day: i64) -> Option<&PathBuf> {
+    if images.is_empty() {
+        return None;
+    }
+    images.get(day.rem_euclid(images.len() as i64) as usize)
+}
+
+impl WallpaperSource for LocalFolderSource {
+    fn fetch_info(&self) -> SourceFuture<'_, ImageMetadata> {
+        Box::pin(async move {
+            let images = self.list_images()?;
+            let today = chrono::Local::now().date_naive();
+            let picked = pick_for_day(&images, today.num_days_from_ce() as i64)
+                .ok_or_else(|| Error::Config(format!("No images found in {}", self.folder.display())))?;
+
+            let ext = picked.extension()
+                .and_then(|e| e.to_str())
+                .map(|e| e.to_lowercase())
+                .unwrap_or_else(|| "jpg".to_string());
+            let title = picked.file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("Wallpaper")
+                .to_string();
+
+            Ok(ImageMetadata {
+                filename: format!("local-{}.{}", today.format("%Y-%m-%d"), ext),
+                title,
+                start_date: today.format("%Y%m%d").to_string(),
+                url: picked.to_string_lossy().to_string(),
+                ..Default::default()
+            })
+        })
+    }
+
+    fn fetch_image<'a>(
+        &'a self,
+        info: &'a ImageMetadata,
+        dest: &'a Path,
+        progress: &'a Progress,
+    ) -> SourceFuture<'a, ()> {
+        Box::pin(async move {
+            // A local copy is quick, so there is nothing worth resuming
+            let size = tokio::fs::copy(Path::new(&info.url), dest)
+                .await
+                .map_err(|e| Error::Io(format!("Failed to copy {}: {e}", info.url)))?;
+            progress(size, Some(size));
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pick_for_day_rotates_daily() {
+        let images: Vec<PathBuf> = ["a.jpg", "b.jpg", "c.jpg"].iter().map(PathBuf::from).collect();
+        assert_eq!(pick_for_day(&images, 0), Some(&images[0]));
+        assert_eq!(pick_for_day(&images, 1), Some(&images[1]));
+        assert_eq!(pick_for_day(&images, 5), Some(&images[2]));
+        assert_eq!(pick_for_day(&[], 5), None);
+    }
+}
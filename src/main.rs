@@ -5,6 +5,7 @@
 //!
 //! ## Features
 //! - Fetches today's Bing image from multiple regional markets
+//! - Can use a local photo folder instead of Bing as the daily source
 //! - Previews images before applying as wallpaper
 //! - Maintains a history of downloaded wallpapers
 //! - Internal timer for automatic daily updates
@@ -19,13 +20,28 @@
 //! - `service.rs` - Wallpaper service (embedded in applet, exposes D-Bus interface)
 //! - `timer.rs` - Internal timer for scheduled fetches
 //! - `dbus_client.rs` - Client proxy for settings window to communicate with applet
+//! - `source.rs` - `WallpaperSource` trait and the shared download pipeline
 //! - `bing.rs` - Bing API client for fetching image metadata and downloading
+//! - `local.rs` - Local folder source (your own photos instead of Bing)
 //! - `config.rs` - User configuration and regional market definitions
+//! - `display.rs` - Connected display detection for automatic image sizing
+//! - `metadata.rs` - Metadata index (title, copyright, link) for downloaded images
+//! - `error.rs` - Error type that tells transient failures (worth a retry) from permanent ones
+//! - `retry.rs` - Retry with exponential backoff and jitter, shared by every fetch path
+//! - `network.rs` - NetworkManager connectivity watch, so scheduled fetches wait for the network
+//! - `naming.rs` - Filename template and date subdirectories for downloaded Bing images
+//! - `process.rs` - Optional post-processing (crop, blur, brightness...) on a cached copy before apply
+//! - `overlay.rs` - Caption overlay (title, date, copyright) drawn onto the processed copy
+//! - `theme.rs` - COSMIC light/dark theme watch, for the darker wallpaper variant
+//! - `outputs.rs` - Per-display wallpapers (another day or market on each display)
+//! - `background.rs` - COSMIC background config (cosmic-bg) read and written as RON, keeping its options
 //!
 //! ## CLI Usage
 //! - No arguments: Run as COSMIC panel applet
 //! - `--settings`, `-s`: Open the settings window
 //! - `--fetch`, `-f`: CLI fetch and apply (one-shot)
+//! - `--backfill`, `-b`: Download any of the last 8 days missing from the archive
+//! - `--revert`, `-r`: Go back to the wallpaper shown before the last change
 //! - `--help`, `-h`: Show help message
 //!
 //! ## Created with Claude
@@ -42,6 +58,23 @@ mod settings;    // Full settings window (launched via --settings)
 mod service;     // D-Bus service + wallpaper apply logic (embedded in the applet)
 mod timer;       // Internal daily timer for automatic wallpaper updates
 mod dbus_client; // D-Bus client proxy (used by settings window to talk to the applet)
+mod display;     // Connected display detection (picks a matching image size)
+mod metadata;    // Per-image metadata index stored next to the wallpapers
+mod source;      // WallpaperSource trait: where the daily image comes from
+mod local;       // Local folder wallpaper source
+mod feed;        // RSS/Atom/JSON feed wallpaper source
+mod http;        // Shared HTTP client setup (proxy, CA bundle, User-Agent)
+mod error;       // Error type that tells transient failures from permanent ones
+mod retry;       // Shared retry/backoff policy for every fetch path
+mod network;     // NetworkManager connectivity watch (defers fetches while offline)
+mod naming;      // Filename template and date subdirectories for downloaded images
+mod process;     // Optional post-processing (crop, blur, dim...) before apply
+mod overlay;     // Caption (title, date, copyright) drawn onto the wallpaper
+mod theme;       // COSMIC theme mode watch (darker wallpaper in dark mode)
+mod outputs;     // Per-display wallpapers for multi-monitor setups
+mod background;  // COSMIC background config, parsed and rewritten with the ron crate
+#[cfg(test)]
+mod test_http;   // Local HTTP stand-in for offline tests
 
 /// Application entry point — dispatches to the appropriate mode based on CLI arguments.
 ///
@@ -68,6 +101,14 @@ fn main() -> cosmic::iced::Result {
                 // Headless mode: fetch today's wallpaper and apply it, then exit
                 run_headless()
             }
+            "--backfill" | "-b" => {
+                // Headless mode: download any archived days we don't have yet, then exit
+                run_backfill()
+            }
+            "--revert" | "-r" => {
+                // Headless mode: undo the last wallpaper change, then exit
+                run_revert()
+            }
             "--help" | "-h" => {
                 print_help(&args[0]);
                 Ok(())
@@ -97,6 +138,8 @@ fn print_help(program: &str) {
     println!("  (none)             Run as COSMIC panel applet");
     println!("  --settings, -s     Open the settings window");
     println!("  --fetch, -f        Fetch and apply wallpaper (one-shot, no GUI)");
+    println!("  --backfill, -b     Download any of the last 8 days missing locally");
+    println!("  --revert, -r       Go back to the wallpaper shown before the last change");
     println!("  --version, -v      Show version information");
     println!("  --help, -h         Show this help message");
     println!();
@@ -104,29 +147,22 @@ fn print_help(program: &str) {
     println!("The settings window connects to the applet via D-Bus.");
 }
 
-/// Maximum number of retry attempts for network operations in headless mode
-const MAX_RETRIES: u32 = 3;
-
-/// Initial delay between retries in seconds.
-/// Uses exponential backoff: 10s, 20s, 40s (doubles each attempt).
-const INITIAL_RETRY_DELAY_SECS: u64 = 10;
-
 /// Runs the application in headless mode (no GUI).
 ///
 /// This mode is used for command-line fetch-and-apply operations,
 /// useful for scripts, cron jobs, or one-shot usage.
 ///
 /// The function creates its own tokio runtime (since there's no COSMIC event loop)
-/// and attempts to fetch and apply the wallpaper with exponential backoff retries
-/// in case of network failures.
+/// and attempts to fetch and apply the wallpaper, retrying network failures
+/// with exponential backoff.
 ///
 /// # Retry Strategy
-/// - Attempt 0: immediate
-/// - Attempt 1: wait 10 seconds
-/// - Attempt 2: wait 20 seconds
+/// Follows the `retry` policy in the config (by default 3 attempts, waiting
+/// about 10 and then 20 seconds). Only transient errors (timeouts, network
+/// failures, HTTP 408/429/5xx) are retried; a permanent error such as a 404
+/// or an invalid image stops immediately.
 fn run_headless() -> cosmic::iced::Result {
     use tokio::runtime::Runtime;
-    use std::time::Duration;
 
     // Create a tokio runtime for async HTTP operations
     let rt = Runtime::new().expect("Failed to create tokio runtime");
@@ -134,55 +170,114 @@ fn run_headless() -> cosmic::iced::Result {
     rt.block_on(async {
         let config = config::Config::load();
 
-        println!("Fetching Bing image for market: {}", config.market);
-
-        let mut last_error = String::new();
-        for attempt in 0..MAX_RETRIES {
-            // Wait before retrying (skip delay on first attempt)
-            if attempt > 0 {
-                // Exponential backoff: 10s, 20s, 40s...
-                // (1 << (attempt - 1)) is a bit shift that gives us powers of 2: 1, 2, 4...
-                let delay = INITIAL_RETRY_DELAY_SECS * (1 << (attempt - 1));
-                println!("Retry {} of {} in {} seconds...", attempt, MAX_RETRIES - 1, delay);
-                tokio::time::sleep(Duration::from_secs(delay)).await;
+        let network = network::status_for(config.metered).await;
+        let source = source::from_config(&config, network);
+        match config.source {
+            config::SourceKind::Bing => println!("Fetching Bing image for market: {}", config.todays_market()),
+            config::SourceKind::LocalFolder => println!("Picking image from folder: {}", config.local_folder),
+            config::SourceKind::Feed => println!("Fetching image from feed: {}", config.feed_url),
+        }
+
+        // Steps 1-2, retried on transient errors
+        let fetched = retry::retry(
+            &config.retry,
+            || async {
+                // Step 1: Fetch image metadata from the configured source
+                let image = source.fetch_info()
+                    .await
+                    .map_err(|e| e.context("Failed to fetch"))?;
+                println!("Found: {}", image.display_title());
+
+                // Step 2: Download the actual image file
+                let path = source::download(source.as_ref(), &image, &config.wallpaper_dir, &http::no_progress)
+                    .await
+                    .map_err(|e| e.context("Failed to download"))?;
+                println!("Downloaded to: {}", path);
+                Ok(path)
+            },
+            |r| eprintln!("{}", r.message()),
+        ).await;
+
+        let path = match fetched {
+            Ok(path) => path,
+            Err(e) => {
+                eprintln!("{}", e);
+                if !e.is_transient() {
+                    // Trying again won't help (e.g., HTTP 404, invalid image, disk full)
+                    eprintln!("Not retried: this error is permanent.");
+                }
+                return;
             }
+        };
+
+        // Images for displays that show another market or day (see outputs.rs)
+        outputs::fetch_images(&config, network, |r| eprintln!("{}", r.message())).await;
+
+        // Step 3: Apply the wallpaper to COSMIC desktop
+        match settings::apply_wallpaper_headless(&path).await {
+            Ok(()) => println!("Wallpaper applied successfully!"),
+            Err(e) => eprintln!("Failed to apply wallpaper: {e}"),
+        }
+    });
+
+    Ok(())
+}
+
+/// Downloads every day from Bing's archive that is missing locally (no GUI).
+///
+/// Bing keeps about 8 days of images. This fills gaps in the wallpaper
+/// directory left by days the machine was offline, without changing the
+/// current wallpaper. Days that fail to download are skipped and listed at
+/// the end, with a non-zero exit status.
+fn run_backfill() -> cosmic::iced::Result {
+    let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+
+    rt.block_on(async {
+        let config = config::Config::load();
+
+        let market = config.todays_market();
+        println!("Backfilling Bing archive for market: {}", market);
 
-            // Step 1: Fetch image metadata from Bing API
-            match bing::fetch_bing_image_info(&config.market).await {
-                Ok(image) => {
-                    println!("Found: {}", image.title);
-
-                    // Step 2: Download the actual image file
-                    match bing::download_image(&image, &config.wallpaper_dir, &config.market).await {
-                        Ok(path) => {
-                            println!("Downloaded to: {}", path);
-
-                            // Step 3: Apply the wallpaper to COSMIC desktop
-                            match settings::apply_wallpaper_headless(&path).await {
-                                Ok(()) => {
-                                    println!("Wallpaper applied successfully!");
-                                    return;  // Success! Exit the retry loop
-                                }
-                                Err(e) => {
-                                    last_error = format!("Failed to apply wallpaper: {e}");
-                                    eprintln!("{}", last_error);
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            last_error = format!("Failed to download: {e}");
-                            eprintln!("{}", last_error);
-                        }
-                    }
+        let settings = bing::ArchiveSettings::from_config(&config, network::status_for(config.metered).await);
+        match bing::backfill_archive(&settings, &market, |r| eprintln!("{}", r.message())).await {
+            Ok(backfill) if backfill.downloaded.is_empty() && backfill.failed.is_empty() => {
+                println!("Archive already complete, nothing to download.")
+            }
+            Ok(backfill) => {
+                for path in &backfill.downloaded {
+                    println!("Downloaded to: {}", path);
                 }
-                Err(e) => {
-                    last_error = format!("Failed to fetch: {e}");
-                    eprintln!("{}", last_error);
+                service::cleanup_old_wallpapers(&config);
+                println!("Downloaded {} missing day(s).", backfill.downloaded.len());
+                if let Some(summary) = backfill.error_summary() {
+                    eprintln!("{summary}");
+                    std::process::exit(1);
                 }
             }
+            Err(e) => {
+                eprintln!("Failed to backfill archive: {e}");
+                std::process::exit(1);
+            }
         }
+    });
 
-        eprintln!("All {} attempts failed. Last error: {}", MAX_RETRIES, last_error);
+    Ok(())
+}
+
+/// Goes back to the wallpaper shown before the last change (`--revert`),
+/// like the applet's Undo button. Reverting again returns to where it started.
+fn run_revert() -> cosmic::iced::Result {
+    let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+
+    rt.block_on(async {
+        match service::revert_cosmic_wallpaper().await {
+            Ok(Some(path)) => println!("Restored: {path}"),
+            Ok(None) => println!("Restored the previous background."),
+            Err(e) => {
+                eprintln!("Failed to restore the previous wallpaper: {e}");
+                std::process::exit(1);
+            }
+        }
     });
 
     Ok(())
@@ -0,0 +1,393 @@
+//! # Image Metadata Index
+//!
+//! Persists the full Bing metadata for every downloaded wallpaper, so history
+//! can show the real title, copyright and source link for old images instead
+//! of only what can be recovered from the filename.
+//!
+//! ## Storage
+//! A single JSON index lives next to the wallpapers:
+//! `<wallpaper_dir>/.index.json`
+//!
+//! The file is hidden so it doesn't show up in photo viewers or synced
+//! libraries, and it is keyed by the image's path relative to the wallpaper
+//! directory (just the filename, unless images are sorted into
+//! [date subdirectories](crate::naming)):
+//! ```json
+//! {
+//!   "images": [
+//!     { "filename": "bing-en-US-2026-02-05.jpg", "title": "...", "hsh": "...", ... }
+//!   ]
+//! }
+//! ```
+//! History and cleanup go through the index ([`stored_images`]), since a
+//! [filename template](crate::naming) can drop the date from the name.
+//! Images without an entry (e.g., downloaded by older versions) still appear
+//! in history, using the date parsed from the filename.
+//!
+//! ## Concurrent Updates
+//! The applet and the settings window are separate processes that both
+//! update the index. Every change ([`record`], [`add_market`], [`forget`],
+//! [`prune`]) loads, modifies and saves it while holding an advisory lock
+//! (`flock`) on `<wallpaper_dir>/.index.json.lock`, so one process can't
+//! overwrite entries the other just added.
+//!
+//! ## Deduplication
+//! Markets often feature the same photo on the same day. Each entry carries
+//! Bing's `hsh` and a SHA-256 of the file contents; [`ImageMetadata::dedup_key`]
+//! picks whichever is available so downloads and history listings can detect
+//! the same image saved under another market's filename.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::bing::BingImage;
+
+/// Filename of the metadata index inside the wallpaper directory.
+pub const INDEX_FILENAME: &str = ".index.json";
+
+/// Filename of the lock file that serializes index updates across processes.
+const LOCK_FILENAME: &str = ".index.json.lock";
+
+/// Everything we know about one downloaded image.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ImageMetadata {
+    /// Image filename inside the wallpaper directory (the index key)
+    pub filename: String,
+    /// Image title/description
+    pub title: String,
+    /// Copyright/attribution text
+    pub copyright: String,
+    /// Link to more information about the image (empty if none)
+    #[serde(default)]
+    pub copyright_link: String,
+    /// Date the image was first featured (format: YYYYMMDD)
+    pub start_date: String,
+    /// Date the image stopped being featured (format: YYYYMMDD)
+    #[serde(default)]
+    pub end_date: String,
+    /// Bing's hash of the image (identical across markets for the same photo)
+    #[serde(default)]
+    pub hsh: String,
+    /// Full URL without the size suffix
+    #[serde(default)]
+    pub urlbase: String,
+    /// URL the image was downloaded from
+    #[serde(default)]
+    pub url: String,
+    /// Market the metadata was fetched for (e.g., "en-US")
+    #[serde(default)]
+    pub market: String,
+    /// SHA-256 of the file contents (hex), used when Bing sends no `hsh`
+    #[serde(default)]
+    pub content_hash: String,
+    /// Image width in pixels, measured when the file was validated
+    #[serde(default)]
+    pub width: u32,
+    /// Image height in pixels, measured when the file was validated
+    #[serde(default)]
+    pub height: u32,
+    /// Market the translated caption was fetched for (empty if none)
+    #[serde(default)]
+    pub caption_market: String,
+    /// Title in the caption language (empty if none)
+    #[serde(default)]
+    pub caption_title: String,
+    /// Copyright text in the caption language (empty if none)
+    #[serde(default)]
+    pub caption_copyright: String,
+    /// Other markets that featured the same photo, whose downloads were
+    /// deduplicated to this file
+    #[serde(default)]
+    pub other_markets: Vec<String>,
+}
+
+impl ImageMetadata {
+    /// Builds the metadata record for a Bing image saved as `filename`.
+    pub fn from_bing(image: &BingImage, filename: &str) -> Self {
+        Self {
+            filename: filename.to_string(),
+            title: image.title.clone(),
+            copyright: image.copyright.clone(),
+            copyright_link: image.copyright_link.clone(),
+            start_date: image.date.clone(),
+            end_date: image.end_date.clone(),
+            hsh: image.hsh.clone(),
+            urlbase: image.urlbase.clone(),
+            url: image.url.clone(),
+            market: image.market.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Stores the title and copyright of the same photo in another market's
+    /// language (see [`crate::bing::fetch_caption`]).
+    pub fn set_caption(&mut self, caption: &BingImage) {
+        self.caption_market = caption.market.clone();
+        self.caption_title = caption.title.clone();
+        self.caption_copyright = caption.copyright.clone();
+    }
+
+    /// Title to show: the translated caption if there is one
+    pub fn display_title(&self) -> &str {
+        if self.caption_title.is_empty() { &self.title } else { &self.caption_title }
+    }
+
+    /// Copyright to show: the translated caption if there is one
+    pub fn display_copyright(&self) -> &str {
+        if self.caption_copyright.is_empty() { &self.copyright } else { &self.caption_copyright }
+    }
+
+    /// Key identifying the same photo across markets: Bing's `hsh` if present,
+    /// otherwise the content hash. None if neither is known.
+    pub fn dedup_key(&self) -> Option<&str> {
+        [self.hsh.as_str(), self.content_hash.as_str()]
+            .into_iter()
+            .find(|key| !key.is_empty())
+    }
+
+    /// Whether the photo was featured in `market` (its own, or one whose
+    /// copy was deduplicated to this file).
+    pub fn featured_in(&self, market: &str) -> bool {
+        self.market.eq_ignore_ascii_case(market)
+            || self.other_markets.iter().any(|m| m.eq_ignore_ascii_case(market))
+    }
+
+    /// Featured date formatted as YYYY-MM-DD (or the raw value if unexpected)
+    pub fn display_date(&self) -> String {
+        if self.start_date.len() == 8 {
+            format!("{}-{}-{}", &self.start_date[0..4], &self.start_date[4..6], &self.start_date[6..8])
+        } else {
+            self.start_date.clone()
+        }
+    }
+}
+
+/// The on-disk index of all downloaded images in a wallpaper directory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MetadataIndex {
+    /// One entry per downloaded image
+    #[serde(default)]
+    pub images: Vec<ImageMetadata>,
+}
+
+impl MetadataIndex {
+    /// Returns the path to the index file for a wallpaper directory
+    fn index_path(wallpaper_dir: &str) -> PathBuf {
+        Path::new(wallpaper_dir).join(INDEX_FILENAME)
+    }
+
+    /// Loads the index, returning an empty one if missing or unreadable
+    pub fn load(wallpaper_dir: &str) -> Self {
+        std::fs::read_to_string(Self::index_path(wallpaper_dir))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Saves the index, writing to a temporary file first so a crash can't
+    /// leave a half-written index behind
+    pub fn save(&self, wallpaper_dir: &str) -> Result<(), String> {
+        let path = Self::index_path(wallpaper_dir);
+        let tmp_path = path.with_extension("json.tmp");
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize metadata index: {e}"))?;
+
+        std::fs::write(&tmp_path, content)
+            .map_err(|e| format!("Failed to write metadata index: {e}"))?;
+        std::fs::rename(&tmp_path, &path)
+            .map_err(|e| format!("Failed to write metadata index: {e}"))
+    }
+
+    /// Looks up the entry for an image filename
+    pub fn get(&self, filename: &str) -> Option<&ImageMetadata> {
+        self.images.iter().find(|m| m.filename == filename)
+    }
+
+    /// Adds an entry, replacing any existing entry for the same filename
+    pub fn upsert(&mut self, metadata: ImageMetadata) {
+        self.images.retain(|m| m.filename != metadata.filename);
+        self.images.push(metadata);
+    }
+
+    /// Finds an existing image in `wallpaper_dir` with the given Bing hash
+    /// or content hash. Entries whose files were deleted are ignored.
+    pub fn find_duplicate(&self, wallpaper_dir: &str, hash: &str) -> Option<&ImageMetadata> {
+        if hash.is_empty() {
+            return None;
+        }
+        self.images.iter().find(|m| {
+            (m.hsh == hash || m.content_hash == hash)
+                && Path::new(wallpaper_dir).join(&m.filename).exists()
+        })
+    }
+
+    /// Removes the entry for an image filename (if any)
+    pub fn remove(&mut self, filename: &str) {
+        self.images.retain(|m| m.filename != filename);
+    }
+}
+
+/// Loads a directory's index, lets `change` modify it and saves it, all
+/// under the index lock (see the [module docs](self#concurrent-updates)).
+///
+/// `change` returns whether it changed anything; if not, nothing is written.
+fn update(wallpaper_dir: &str, change: impl FnOnce(&mut MetadataIndex) -> bool) -> Result<(), String> {
+    // Released when the file is closed at the end of this function
+    let lock = std::fs::File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(Path::new(wallpaper_dir).join(LOCK_FILENAME))
+        .and_then(|file| file.lock().map(|()| file));
+    if let Err(e) = &lock {
+        // Better an unlocked update than none at all
+        eprintln!("Failed to lock the metadata index: {e}");
+    }
+
+    let mut index = MetadataIndex::load(wallpaper_dir);
+    if change(&mut index) {
+        index.save(wallpaper_dir)?;
+    }
+    Ok(())
+}
+
+/// Records metadata for a downloaded image in its directory's index.
+pub fn record(wallpaper_dir: &str, metadata: ImageMetadata) -> Result<(), String> {
+    update(wallpaper_dir, |index| {
+        index.upsert(metadata);
+        true
+    })
+}
+
+/// Finds the index that has an entry for an image, given its full path.
+///
+/// Images in date subdirectories are indexed one or two levels up, by their
+/// relative path.
+///
+/// # Returns
+/// The wallpaper directory, its index and the image's key in it
+fn locate(image_path: &str) -> Option<(String, MetadataIndex, String)> {
+    let path = Path::new(image_path);
+    path.ancestors().skip(1).take(3).find_map(|dir| {
+        let key = path.strip_prefix(dir).ok()?.to_str()?;
+        let dir = dir.to_str()?;
+        let index = MetadataIndex::load(dir);
+        index.get(key)?;
+        Some((dir.to_string(), index, key.to_string()))
+    })
+}
+
+/// Looks up the metadata for an image by its full path.
+pub fn lookup(image_path: &str) -> Option<ImageMetadata> {
+    let (_, index, key) = locate(image_path)?;
+    index.get(&key).cloned()
+}
+
+/// Notes that `market` featured the image at `image_path` too, for a
+/// download that was deduplicated to an existing file.
+pub fn add_market(image_path: &str, market: &str) {
+    if market.is_empty() {
+        return;
+    }
+    let Some((dir, _, key)) = locate(image_path) else {
+        return;
+    };
+    let _ = update(&dir, |index| {
+        match index.images.iter_mut().find(|m| m.filename == key) {
+            Some(metadata) if !metadata.featured_in(market) => {
+                metadata.other_markets.push(market.to_string());
+                true
+            }
+            _ => false,
+        }
+    });
+}
+
+/// Removes the index entry for an image that was deleted by full path.
+pub fn forget(image_path: &str) {
+    if let Some((dir, _, key)) = locate(image_path) {
+        let _ = update(&dir, |index| {
+            index.remove(&key);
+            true
+        });
+    }
+}
+
+/// Every image stored in a wallpaper directory, with its index entry if it
+/// has one.
+///
+/// Indexed images are found wherever they are stored (including date
+/// subdirectories). Images without an entry (e.g., downloaded by older
+/// versions) are picked up from the top level of the directory.
+pub fn stored_images(wallpaper_dir: &str) -> Vec<(PathBuf, Option<ImageMetadata>)> {
+    let dir = Path::new(wallpaper_dir);
+    let index = MetadataIndex::load(wallpaper_dir);
+
+    let mut images: Vec<(PathBuf, Option<ImageMetadata>)> = index.images
+        .iter()
+        .map(|m| (dir.join(&m.filename), Some(m.clone())))
+        .filter(|(path, _)| path.is_file())
+        .collect();
+
+    let unindexed = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension().is_some_and(|ext| ext == "jpg" || ext == "jpeg" || ext == "png")
+                && path.file_name()
+                    .and_then(|f| f.to_str())
+                    .is_some_and(|f| !f.starts_with('.') && index.get(f).is_none())
+        });
+    images.extend(unindexed.map(|path| (path, None)));
+    images
+}
+
+/// Drops index entries whose image files no longer exist.
+pub fn prune(wallpaper_dir: &str) {
+    let _ = update(wallpaper_dir, |index| {
+        let before = index.images.len();
+        index.images.retain(|m| Path::new(wallpaper_dir).join(&m.filename).exists());
+        index.images.len() != before
+    });
+}
+
+/// Hex-encoded SHA-256 of an image's bytes.
+pub fn content_hash(bytes: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_concurrent_records_are_all_kept() {
+        let dir = std::env::temp_dir().join(format!("bing-wallpaper-index-lock-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let wallpaper_dir = dir.to_str().unwrap().to_string();
+
+        // Stands in for the applet and the settings window updating the index at once
+        let writers: Vec<_> = (0..4)
+            .map(|writer| {
+                let wallpaper_dir = wallpaper_dir.clone();
+                std::thread::spawn(move || {
+                    for i in 0..20 {
+                        let filename = format!("bing-{writer}-{i}.jpg");
+                        record(&wallpaper_dir, ImageMetadata { filename, ..Default::default() }).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        assert_eq!(MetadataIndex::load(&wallpaper_dir).images.len(), 80);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
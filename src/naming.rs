@@ -0,0 +1,196 @@
+//! # File Naming
+//!
+//! Builds the path a Bing image is stored under inside the wallpaper
+//! directory, from [`Config::filename_template`] and
+//! [`Config::subdirectories`].
+//!
+//! ## Template
+//! The template is the filename without its extension. Placeholders:
+//!
+//! | Placeholder | Value | Example |
+//! |-------------|-------|---------|
+//! | `{date}` | Date the image was featured | `2026-02-05` |
+//! | `{market}` | Market the image was fetched for | `en-US` |
+//! | `{title}` | Title (in the caption language, if one is set) | `Northern lights over Lapland` |
+//! | `{hsh}` | Bing's hash of the image | `6e1b3c...` |
+//! | `{resolution}` | Configured Bing size (`auto` for Auto) | `UHD` |
+//!
+//! The default, `bing-{market}-{date}`, gives the names used before the
+//! template existed. Values are cleaned up so the name is valid on any file
+//! system a photo library might sync to, and unknown placeholders are kept
+//! as written. `{resolution}` is the size asked for: the download may fall
+//! back to a smaller one (see [`crate::bing`]). Every day needs its own name,
+//! so a template without `{date}` (or `{hsh}`, when Bing sends one) gets
+//! `-{date}` appended.
+//!
+//! ## Subdirectories
+//! Images can be sorted into `2026/` or `2026/02/` folders by their featured
+//! date. The metadata index is keyed by the path relative to the wallpaper
+//! directory (e.g., `2026/02/bing-en-US-2026-02-05.jpg`), which is how history
+//! and cleanup find images wherever they are stored.
+
+use crate::config::{Config, Subdirectories};
+use crate::metadata::ImageMetadata;
+
+/// Template giving the original `bing-{market}-{date}.jpg` names.
+pub const DEFAULT_TEMPLATE: &str = "bing-{market}-{date}";
+
+/// Longest title kept in a filename, in characters.
+const MAX_TITLE_CHARS: usize = 80;
+
+/// Characters that aren't allowed in filenames on common file systems.
+const FORBIDDEN: &[char] = &[':', '*', '?', '"', '<', '>', '|'];
+
+/// How Bing images are named inside the wallpaper directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileNaming {
+    /// Filename template without the extension
+    pub template: String,
+    /// Date folders to sort images into
+    pub subdirectories: Subdirectories,
+}
+
+impl Default for FileNaming {
+    /// The original layout: `bing-{market}-{date}.jpg`, no subdirectories
+    fn default() -> Self {
+        Self { template: DEFAULT_TEMPLATE.to_string(), subdirectories: Subdirectories::None }
+    }
+}
+
+impl FileNaming {
+    /// The naming configured by the user.
+    pub fn from_config(config: &Config) -> Self {
+        Self { template: config.filename_template.clone(), subdirectories: config.subdirectories }
+    }
+
+    /// The path (relative to the wallpaper directory) to store a Bing image
+    /// under, e.g. `2026/02/bing-en-US-2026-02-05.jpg`.
+    ///
+    /// Uses `info`'s date, market, title and hash; `resolution` is the
+    /// configured Bing size.
+    pub fn path_for(&self, info: &ImageMetadata, resolution: &str) -> String {
+        let filename = format!("{}.jpg", render(&self.template, info, resolution));
+        match subdirectory(self.subdirectories, &info.start_date) {
+            Some(dir) => format!("{dir}/{filename}"),
+            None => filename,
+        }
+    }
+}
+
+/// Fills in the template's placeholders (see the [module docs](self)).
+fn render(template: &str, info: &ImageMetadata, resolution: &str) -> String {
+    let template = template.trim();
+    let template = if template.is_empty() { DEFAULT_TEMPLATE } else { template };
+    let unique = template.contains("{date}") || (template.contains("{hsh}") && !info.hsh.is_empty());
+    let template = if unique { template.to_string() } else { format!("{template}-{{date}}") };
+
+    let value = |name: &str| -> Option<String> {
+        match name {
+            "date" => Some(info.display_date()),
+            "market" => Some(info.market.clone()),
+            "title" => Some(info.display_title().chars().take(MAX_TITLE_CHARS).collect()),
+            "hsh" => Some(info.hsh.clone()),
+            "resolution" => Some(resolution.to_string()),
+            _ => None,
+        }
+    };
+
+    // One pass, so braces inside a title are never taken for placeholders
+    let mut name = String::new();
+    let mut rest = template.as_str();
+    while let Some(start) = rest.find('{') {
+        name.push_str(&rest[..start]);
+        let placeholder = &rest[start..];
+        match placeholder.find('}').and_then(|end| Some((end, value(&placeholder[1..end])?))) {
+            Some((end, value)) => {
+                name.push_str(&value);
+                rest = &placeholder[end + 1..];
+            }
+            None => {
+                name.push('{');
+                rest = &placeholder[1..];
+            }
+        }
+    }
+    name.push_str(rest);
+
+    let name = clean(&name);
+    if name.is_empty() { info.display_date() } else { name }
+}
+
+/// Makes a string safe to use as a single filename: path separators become
+/// dashes, forbidden and control characters are dropped, whitespace is
+/// collapsed, and leading dots (hidden files) and trailing dots are trimmed.
+fn clean(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c == '/' || c == '\\' { '-' } else { c })
+        .filter(|c| !FORBIDDEN.contains(c) && !c.is_control())
+        .collect();
+    name.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_matches(|c: char| c == '.' || c == ' ')
+        .to_string()
+}
+
+/// The date folder for an image featured on `start_date` (YYYYMMDD), or
+/// None without subdirectories or if the date is malformed.
+fn subdirectory(subdirectories: Subdirectories, start_date: &str) -> Option<String> {
+    if start_date.len() != 8 || !start_date.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let (year, month) = (&start_date[0..4], &start_date[4..6]);
+    match subdirectories {
+        Subdirectories::None => None,
+        Subdirectories::Year => Some(year.to_string()),
+        Subdirectories::Month => Some(format!("{year}/{month}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info() -> ImageMetadata {
+        ImageMetadata {
+            title: "Lapland: Northern lights / aurora".to_string(),
+            start_date: "20260205".to_string(),
+            market: "en-US".to_string(),
+            hsh: "6e1b3c".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_render_template() {
+        let info = info();
+        assert_eq!(FileNaming::default().path_for(&info, "UHD"), "bing-en-US-2026-02-05.jpg");
+
+        let naming = |template: &str| FileNaming { template: template.to_string(), subdirectories: Subdirectories::None };
+        assert_eq!(
+            naming("{date} {title} ({resolution})").path_for(&info, "UHD"),
+            "2026-02-05 Lapland Northern lights - aurora (UHD).jpg"
+        );
+        assert_eq!(naming("{hsh}").path_for(&info, "UHD"), "6e1b3c.jpg");
+        assert_eq!(naming("{title}{unknown}").path_for(&info, "UHD"),
+            "Lapland Northern lights - aurora{unknown}-2026-02-05.jpg");
+
+        // Braces in a value aren't placeholders; the date keeps names unique
+        let braces = ImageMetadata { title: "{date}".to_string(), hsh: String::new(), ..info.clone() };
+        assert_eq!(naming("{title}{hsh}").path_for(&braces, "UHD"), "{date}-2026-02-05.jpg");
+        assert_eq!(naming("  ").path_for(&info, "UHD"), "bing-en-US-2026-02-05.jpg");
+        assert_eq!(naming("../{date}").path_for(&info, "UHD"), "-2026-02-05.jpg");
+    }
+
+    #[test]
+    fn test_subdirectories() {
+        let info = info();
+        let naming = |subdirectories| FileNaming { subdirectories, ..FileNaming::default() };
+        assert_eq!(naming(Subdirectories::Year).path_for(&info, "UHD"), "2026/bing-en-US-2026-02-05.jpg");
+        assert_eq!(naming(Subdirectories::Month).path_for(&info, "UHD"), "2026/02/bing-en-US-2026-02-05.jpg");
+
+        let undated = ImageMetadata { start_date: "today".to_string(), ..info };
+        assert_eq!(subdirectory(Subdirectories::Month, &undated.start_date), None);
+    }
+}
@@ -0,0 +1,334 @@
+//! # Network Connectivity
+//!
+//! Watches NetworkManager's connectivity state over D-Bus, so scheduled
+//! fetches can wait for the network instead of failing while it is down
+//! (e.g., right after resume, before Wi-Fi has reconnected), and whether the
+//! connection is metered (e.g., phone tethering), for the metered policy.
+//!
+//! NetworkManager publishes a `Connectivity` property on
+//! `org.freedesktop.NetworkManager` (system bus) and announces changes with
+//! `PropertiesChanged`:
+//!
+//! | Value | Meaning | Online |
+//! |-------|---------|--------|
+//! | 0 | Unknown (connectivity checking disabled or not done yet) | yes |
+//! | 1 | None: not connected | no |
+//! | 2 | Portal: behind a captive portal (e.g., hotel Wi-Fi login) | no |
+//! | 3 | Limited: connected, but the internet isn't reachable | no |
+//! | 4 | Full: internet reachable | yes |
+//!
+//! "Unknown" counts as online so a machine without connectivity checking
+//! still gets its wallpaper. When NetworkManager isn't running at all the
+//! monitor always reports online and unmetered, which is the behavior before
+//! this module existed.
+//!
+//! The `Metered` property says whether the primary connection costs money
+//! per byte: 1 (yes) and 3 (guessed yes, e.g., a phone hotspot) count as
+//! metered; 0 (unknown), 2 (no) and 4 (guessed no) don't.
+
+use futures_util::StreamExt;
+use tokio::sync::watch;
+use zbus::{proxy, Connection};
+
+use crate::config::MeteredPolicy;
+
+/// D-Bus proxy for the parts of NetworkManager we need
+#[proxy(
+    interface = "org.freedesktop.NetworkManager",
+    default_service = "org.freedesktop.NetworkManager",
+    default_path = "/org/freedesktop/NetworkManager"
+)]
+trait NetworkManager {
+    /// Overall connectivity (see the table in the module docs)
+    #[zbus(property)]
+    fn connectivity(&self) -> zbus::Result<u32>;
+
+    /// Whether the primary connection is metered (`NMMetered`)
+    #[zbus(property)]
+    fn metered(&self) -> zbus::Result<u32>;
+}
+
+/// NetworkManager's `NMConnectivityState`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connectivity {
+    Unknown,
+    None,
+    Portal,
+    Limited,
+    Full,
+}
+
+impl From<u32> for Connectivity {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => Connectivity::None,
+            2 => Connectivity::Portal,
+            3 => Connectivity::Limited,
+            4 => Connectivity::Full,
+            _ => Connectivity::Unknown,
+        }
+    }
+}
+
+impl Connectivity {
+    /// Whether a fetch is worth trying.
+    pub fn is_online(self) -> bool {
+        matches!(self, Connectivity::Full | Connectivity::Unknown)
+    }
+
+    /// Human-readable description for status messages
+    pub fn label(self) -> &'static str {
+        match self {
+            Connectivity::Unknown => "connectivity unknown",
+            Connectivity::None => "offline",
+            Connectivity::Portal => "behind a captive portal",
+            Connectivity::Limited => "limited connectivity",
+            Connectivity::Full => "online",
+        }
+    }
+}
+
+/// Whether `NMMetered` means the connection costs money per byte.
+fn is_metered(value: u32) -> bool {
+    matches!(value, 1 | 3)
+}
+
+/// A snapshot of the network state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NetworkStatus {
+    /// Whether (and how well) we're connected
+    pub connectivity: Connectivity,
+    /// Whether the primary connection is metered
+    pub metered: bool,
+}
+
+impl Default for NetworkStatus {
+    /// Online and unmetered, as assumed without NetworkManager
+    fn default() -> Self {
+        Self { connectivity: Connectivity::Unknown, metered: false }
+    }
+}
+
+/// Asks NetworkManager for the current network state once, without watching
+/// for changes. Returns the default (online, unmetered) if it can't be reached.
+pub async fn current_status() -> NetworkStatus {
+    let query = async {
+        let conn = Connection::system().await?;
+        let proxy = NetworkManagerProxy::new(&conn).await?;
+        Ok::<_, zbus::Error>(NetworkStatus {
+            connectivity: Connectivity::from(proxy.connectivity().await?),
+            metered: proxy.metered().await.map(is_metered).unwrap_or(false),
+        })
+    };
+    query.await.unwrap_or_default()
+}
+
+/// The network state a download under the `metered` policy needs: the
+/// [current status](current_status) if the policy depends on it (`Reduce`),
+/// otherwise the default, without asking NetworkManager.
+pub async fn status_for(metered: MeteredPolicy) -> NetworkStatus {
+    match metered {
+        MeteredPolicy::Reduce => current_status().await,
+        _ => NetworkStatus::default(),
+    }
+}
+
+/// Follows NetworkManager's connectivity and metered state.
+///
+/// Cheap to clone; every clone sees the same updates.
+#[derive(Debug, Clone)]
+pub struct NetworkMonitor {
+    status: watch::Receiver<NetworkStatus>,
+}
+
+impl NetworkMonitor {
+    /// Watches NetworkManager on the system bus, or always reports online
+    /// if it can't be reached.
+    pub async fn system() -> Self {
+        let watched = match Connection::system().await {
+            Ok(conn) => Self::watch(&conn).await,
+            Err(e) => Err(e),
+        };
+        watched.unwrap_or_else(|e| {
+            eprintln!("NetworkManager not available ({e}), assuming the network is up");
+            Self::always_online()
+        })
+    }
+
+    /// Watches the NetworkManager service reachable through `conn`.
+    ///
+    /// Takes any connection so tests can talk to a mock service on a private
+    /// connection. Fails if the service doesn't answer.
+    pub async fn watch(conn: &Connection) -> zbus::Result<Self> {
+        let proxy = NetworkManagerProxy::new(conn).await?;
+        let mut connectivity_changes = proxy.receive_connectivity_changed().await;
+        let mut metered_changes = proxy.receive_metered_changed().await;
+        let initial = NetworkStatus {
+            connectivity: Connectivity::from(proxy.connectivity().await?),
+            // Older NetworkManager versions may lack the property
+            metered: proxy.metered().await.map(is_metered).unwrap_or(false),
+        };
+        let (tx, rx) = watch::channel(initial);
+
+        tokio::spawn(async move {
+            loop {
+                // One `PropertiesChanged` can carry both properties, and the
+                // streams are fed from the proxy's cache, so after either
+                // change re-read both from the cache: a status is never
+                // published with one property updated and the other stale.
+                tokio::select! {
+                    biased;
+                    Some(_) = metered_changes.next() => {}
+                    Some(_) = connectivity_changes.next() => {}
+                    else => break,
+                }
+                let previous = *tx.borrow();
+                let status = NetworkStatus {
+                    connectivity: match proxy.cached_connectivity() {
+                        Ok(Some(value)) => Connectivity::from(value),
+                        _ => previous.connectivity,
+                    },
+                    metered: match proxy.cached_metered() {
+                        Ok(Some(value)) => is_metered(value),
+                        _ => previous.metered,
+                    },
+                };
+                if tx.send_replace(status) != status {
+                    eprintln!(
+                        "Network: {}{}",
+                        status.connectivity.label(),
+                        if status.metered { ", metered" } else { "" },
+                    );
+                }
+            }
+        });
+
+        Ok(Self { status: rx })
+    }
+
+    /// A monitor that always reports online and unmetered (no NetworkManager).
+    pub fn always_online() -> Self {
+        let (_tx, rx) = watch::channel(NetworkStatus::default());
+        Self { status: rx }
+    }
+
+    /// The current network state
+    pub fn status(&self) -> NetworkStatus {
+        *self.status.borrow()
+    }
+
+    /// The current connectivity
+    pub fn connectivity(&self) -> Connectivity {
+        self.status().connectivity
+    }
+
+    /// Whether a fetch is worth trying right now
+    pub fn is_online(&self) -> bool {
+        self.connectivity().is_online()
+    }
+
+    /// Whether the primary connection is metered
+    pub fn is_metered(&self) -> bool {
+        self.status().metered
+    }
+
+    /// Waits until the network is online (returns at once if it already is).
+    pub async fn wait_until_online(&mut self) {
+        self.wait_until(|s| s.connectivity.is_online()).await;
+    }
+
+    /// Waits until the network state satisfies `condition`.
+    pub async fn wait_until(&mut self, condition: impl FnMut(&NetworkStatus) -> bool) {
+        // Only fails if the watcher task ended; nothing more will change then
+        let _ = self.status.wait_for(condition).await;
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Stand-in for NetworkManager, served on a private connection
+    /// (also used by the timer's tests)
+    pub(crate) struct MockNetworkManager {
+        pub(crate) connectivity: u32,
+        pub(crate) metered: u32,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.NetworkManager")]
+    impl MockNetworkManager {
+        #[zbus(property)]
+        fn connectivity(&self) -> u32 {
+            self.connectivity
+        }
+
+        #[zbus(property)]
+        fn metered(&self) -> u32 {
+            self.metered
+        }
+    }
+
+    pub(crate) const PATH: &str = "/org/freedesktop/NetworkManager";
+
+    /// Connects a client to a mock NetworkManager reporting `connectivity`.
+    pub(crate) async fn mock_network_manager(connectivity: u32) -> (Connection, Connection) {
+        let (server_stream, client_stream) = std::os::unix::net::UnixStream::pair().unwrap();
+        let guid = zbus::Guid::generate();
+        let server = zbus::connection::Builder::unix_stream(server_stream)
+            .server(guid)
+            .unwrap()
+            .p2p()
+            .serve_at(PATH, MockNetworkManager { connectivity, metered: 4 })
+            .unwrap()
+            .build();
+        let client = zbus::connection::Builder::unix_stream(client_stream).p2p().build();
+        let (server, client) = tokio::join!(server, client);
+        (server.unwrap(), client.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_waits_for_full_connectivity() {
+        let (server, client) = mock_network_manager(1).await;
+        let mut monitor = NetworkMonitor::watch(&client).await.unwrap();
+        assert_eq!(monitor.connectivity(), Connectivity::None);
+        assert!(!monitor.is_online());
+
+        let waiting = tokio::spawn(async move {
+            monitor.wait_until_online().await;
+            monitor.connectivity()
+        });
+
+        // Behind a captive portal still counts as offline; full connectivity doesn't
+        let iface = server.object_server().interface::<_, MockNetworkManager>(PATH).await.unwrap();
+        for connectivity in [2, 4] {
+            iface.get_mut().await.connectivity = connectivity;
+            iface.get().await.connectivity_changed(iface.signal_context()).await.unwrap();
+        }
+
+        let connectivity = tokio::time::timeout(Duration::from_secs(5), waiting).await.unwrap().unwrap();
+        assert_eq!(connectivity, Connectivity::Full);
+    }
+
+    #[tokio::test]
+    async fn test_follows_metered_state() {
+        let (server, client) = mock_network_manager(4).await;
+        let mut monitor = NetworkMonitor::watch(&client).await.unwrap();
+        assert!(!monitor.is_metered());
+
+        // Guessed metered (e.g., a phone hotspot)
+        let iface = server.object_server().interface::<_, MockNetworkManager>(PATH).await.unwrap();
+        iface.get_mut().await.metered = 3;
+        iface.get().await.metered_changed(iface.signal_context()).await.unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), monitor.wait_until(|s| s.metered)).await.unwrap();
+        assert!(monitor.is_online());
+    }
+
+    #[tokio::test]
+    async fn test_without_network_manager_is_online() {
+        let mut monitor = NetworkMonitor::always_online();
+        assert!(monitor.is_online());
+        tokio::time::timeout(Duration::from_secs(1), monitor.wait_until_online()).await.unwrap();
+    }
+}
@@ -0,0 +1,154 @@
+//! # Per-Display Wallpapers
+//!
+//! Lets every display (COSMIC calls them outputs) show its own image instead
+//! of the same one on all of them, e.g. today's image on the main screen and
+//! yesterday's on the second, or another market's image on each.
+//!
+//! [`Config::outputs`] lists the displays with their choice. Each choice is
+//! relative to the main wallpaper (the one being applied): `days_ago` counts
+//! back from its featured date, and an empty `market` means its market. The
+//! images come from the wallpaper directory, found through the metadata
+//! index; [`fetch_images`] downloads the ones the daily fetch needs. A
+//! display whose image isn't stored (e.g., a day older than Bing's archive
+//! when applying from history) shows the main wallpaper.
+//!
+//! Display names are the DRM connector names (`DP-1`, `HDMI-A-1`, ...),
+//! which is what COSMIC uses too, plus any display COSMIC already has its
+//! own background entry for (e.g., one that is currently unplugged).
+
+use std::path::PathBuf;
+
+use chrono::{Local, NaiveDate};
+
+use crate::bing;
+use crate::config::{Config, SourceKind};
+use crate::metadata::{self, ImageMetadata};
+use crate::network::NetworkStatus;
+use crate::retry::{self, Retry};
+
+/// Names of the displays a wallpaper can be assigned to, sorted.
+pub fn available_outputs() -> Vec<String> {
+    let mut names: Vec<String> = crate::display::connected_displays().into_iter().map(|d| d.name).collect();
+    names.extend(cosmic_outputs());
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Displays listed in COSMIC's `backgrounds` key (e.g., `["DP-1", "HDMI-A-1"]`).
+fn cosmic_outputs() -> Vec<String> {
+    crate::background::read_key("backgrounds").unwrap_or_default()
+}
+
+/// Downloads the images that displays with their own market or day need
+/// today (Bing only; other sources have a single image a day).
+///
+/// Each download is retried by the [retry policy](crate::retry), with
+/// `on_retry` told before each wait. Failures are logged rather than
+/// returned: those displays then show the main wallpaper, which is already
+/// in place. `network` is for the metered policy (see
+/// [`crate::network::status_for`]).
+pub async fn fetch_images(config: &Config, network: NetworkStatus, on_retry: impl Fn(&Retry)) {
+    if config.source != SourceKind::Bing {
+        return;
+    }
+
+    // Several displays may want the same image
+    let todays_market = config.todays_market();
+    let mut wanted: Vec<(String, u32)> = Vec::new();
+    for output in config.outputs.iter().filter(|o| o.differs()) {
+        let market = if output.market.is_empty() { todays_market.clone() } else { output.market.clone() };
+        if !wanted.contains(&(market.clone(), output.days_ago)) {
+            wanted.push((market, output.days_ago));
+        }
+    }
+
+    let settings = bing::ArchiveSettings::from_config(config, network);
+    for (market, idx) in wanted {
+        let result = retry::retry(&config.retry, || bing::download_day(&settings, &market, idx), &on_retry).await;
+        if let Err(e) = result {
+            eprintln!("Failed to download the {market} image from {idx} day(s) ago: {e}");
+        }
+    }
+}
+
+/// The image each configured display should show when `primary` is applied,
+/// as (output name, image path) pairs. Empty when every display shows the
+/// same wallpaper.
+pub fn assign(config: &Config, primary: &str) -> Vec<(String, String)> {
+    if config.outputs.is_empty() {
+        return Vec::new();
+    }
+
+    let primary_info = metadata::lookup(primary);
+    let date = primary_info
+        .as_ref()
+        .and_then(|m| NaiveDate::parse_from_str(&m.start_date, "%Y%m%d").ok())
+        .unwrap_or_else(|| Local::now().date_naive());
+    let primary_market = primary_info.map(|m| m.market).unwrap_or_default();
+    let images = metadata::stored_images(&config.wallpaper_dir);
+
+    config.outputs
+        .iter()
+        .filter(|output| !output.output.is_empty())
+        .map(|output| {
+            let path = if output.differs() {
+                let market = if output.market.is_empty() { &primary_market } else { &output.market };
+                let day = date - chrono::Duration::days(output.days_ago as i64);
+                find_image(&images, market, day).unwrap_or_else(|| {
+                    eprintln!("No {market} image from {day} for {}, using the main wallpaper", output.output);
+                    primary.to_string()
+                })
+            } else {
+                primary.to_string()
+            };
+            (output.output.clone(), path)
+        })
+        .collect()
+}
+
+/// Finds the stored image featured on `date` in `market` (any market if empty).
+fn find_image(images: &[(PathBuf, Option<ImageMetadata>)], market: &str, date: NaiveDate) -> Option<String> {
+    let date = date.format("%Y%m%d").to_string();
+    images
+        .iter()
+        .find(|(_, info)| {
+            info.as_ref()
+                .is_some_and(|m| m.start_date == date && (market.is_empty() || m.featured_in(market)))
+        })
+        .map(|(path, _)| path.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stored(filename: &str, market: &str, start_date: &str) -> (PathBuf, Option<ImageMetadata>) {
+        let info = ImageMetadata {
+            filename: filename.to_string(),
+            market: market.to_string(),
+            start_date: start_date.to_string(),
+            ..Default::default()
+        };
+        (PathBuf::from("/pictures").join(filename), Some(info))
+    }
+
+    #[test]
+    fn test_find_image_by_market_and_day() {
+        let mut shared = stored("bing-en-GB-2026-02-05.jpg", "en-GB", "20260205");
+        shared.1.as_mut().unwrap().other_markets.push("en-US".to_string());
+        let images = vec![
+            stored("bing-ja-JP-2026-02-05.jpg", "ja-JP", "20260205"),
+            stored("bing-ja-JP-2026-02-04.jpg", "ja-JP", "20260204"),
+            shared,
+            (PathBuf::from("/pictures/unindexed.jpg"), None),
+        ];
+        let day = |d| NaiveDate::from_ymd_opt(2026, 2, d).unwrap();
+
+        assert_eq!(find_image(&images, "ja-JP", day(4)).as_deref(), Some("/pictures/bing-ja-JP-2026-02-04.jpg"));
+        // A market whose copy was deduplicated into another market's file
+        assert_eq!(find_image(&images, "en-US", day(5)).as_deref(), Some("/pictures/bing-en-GB-2026-02-05.jpg"));
+        assert_eq!(find_image(&images, "de-DE", day(5)), None);
+        assert_eq!(find_image(&images, "", day(4)).as_deref(), Some("/pictures/bing-ja-JP-2026-02-04.jpg"));
+    }
+}
@@ -0,0 +1,336 @@
+//! # Caption Overlay
+//!
+//! Draws the image's title, date and copyright onto the wallpaper, the way
+//! Windows shows the Bing caption on the desktop. It is drawn onto the
+//! [processed copy](crate::process) (never the downloaded original) as the
+//! last step, so it stays sharp when the photo is blurred or dimmed.
+//!
+//! ## Layout
+//! ```text
+//!                        ┌──────────────────────────────┐
+//!                        │ Northern lights over Lapland │  title
+//!                        │ 2026-02-05 · © Photographer  │  date · copyright
+//!                        └──────────────────────────────┘
+//! ```
+//! The caption sits in the configured corner on a dark box (the "scrim") that
+//! keeps it readable on bright photos. Sizes are given for a 1080-pixel-high
+//! image and scaled with the actual height, since cosmic-bg zooms the image
+//! to fill the screen. Long lines wrap at 40% of the image width.
+//!
+//! ## Font
+//! The desktop's sans-serif font is found through fontconfig (`fc-match`),
+//! with a few common font files as fallback. Without any usable font, the
+//! caption is left out and the rest of the processing still applies.
+
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use ab_glyph::{point, Font, FontVec, PxScale, ScaleFont};
+
+use crate::config::{Overlay, OverlayPosition};
+use crate::error::Error;
+use crate::metadata::ImageMetadata;
+
+/// Fonts tried when fontconfig has no answer.
+const FALLBACK_FONTS: &[&str] = &[
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/TTF/DejaVuSans.ttf",
+    "/usr/share/fonts/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/open-sans/OpenSans-Regular.ttf",
+    "/usr/share/fonts/truetype/noto/NotoSans-Regular.ttf",
+];
+
+/// Image height that [`Overlay::font_size`] is given for.
+const REFERENCE_HEIGHT: f32 = 1080.0;
+
+/// Size of the date and copyright line relative to the title.
+const DETAIL_SCALE: f32 = 0.7;
+
+/// Widest a caption line may get, as a fraction of the image width.
+const MAX_WIDTH: f32 = 0.4;
+
+/// Distance from the image edges and padding inside the scrim, at 1080 pixels.
+const MARGIN: f32 = 32.0;
+const PADDING: f32 = 14.0;
+
+/// One line of caption text at its size in pixels.
+struct Line {
+    text: String,
+    size: f32,
+}
+
+/// Draws the caption for `info` onto `image`.
+///
+/// # Returns
+/// * `Ok(())` - The caption was drawn (or there was nothing to draw)
+/// * `Err(Error)` - No usable font was found
+pub fn draw_caption(image: &mut image::RgbImage, info: &ImageMetadata, overlay: &Overlay) -> Result<(), Error> {
+    let font = system_font().ok_or_else(|| Error::Config("No font found for the caption overlay".to_string()))?;
+    draw_caption_with(image, font, info, overlay);
+    Ok(())
+}
+
+/// Draws the caption with a given font (separate from [`draw_caption`] so tests can pick the font).
+fn draw_caption_with(image: &mut image::RgbImage, font: &FontVec, info: &ImageMetadata, overlay: &Overlay) {
+    let (width, height) = image.dimensions();
+    let scale = height as f32 / REFERENCE_HEIGHT;
+    let title_size = overlay.font_size.max(1) as f32 * scale;
+    let max_width = width as f32 * MAX_WIDTH;
+
+    let details = [info.display_date(), info.display_copyright().to_string()]
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" \u{00B7} ");
+    let mut lines = Vec::new();
+    for (text, size) in [(info.display_title(), title_size), (details.as_str(), title_size * DETAIL_SCALE)] {
+        lines.extend(wrap(font, size, text, max_width).into_iter().map(|text| Line { text, size }));
+    }
+    if lines.is_empty() {
+        return;
+    }
+
+    // Size of the text block, then its top-left corner in the chosen corner of the image
+    let line_height = |line: &Line| font.as_scaled(PxScale::from(line.size)).height();
+    let block_width = lines.iter().map(|l| text_width(font, l.size, &l.text)).fold(0.0, f32::max);
+    let block_height: f32 = lines.iter().map(line_height).sum();
+    let (margin, padding) = (MARGIN * scale, PADDING * scale);
+    let left = match overlay.position {
+        OverlayPosition::TopLeft | OverlayPosition::BottomLeft => margin + padding,
+        OverlayPosition::TopRight | OverlayPosition::BottomRight => width as f32 - margin - padding - block_width,
+    };
+    let top = match overlay.position {
+        OverlayPosition::TopLeft | OverlayPosition::TopRight => margin + padding,
+        OverlayPosition::BottomLeft | OverlayPosition::BottomRight => height as f32 - margin - padding - block_height,
+    };
+
+    let scrim = overlay.scrim.min(100) as f32 / 100.0;
+    if scrim > 0.0 {
+        fill_rect(
+            image,
+            (left - padding, top - padding),
+            (left + block_width + padding, top + block_height + padding),
+            scrim,
+        );
+    }
+
+    let opacity = overlay.opacity.min(100) as f32 / 100.0;
+    let mut y = top;
+    for line in &lines {
+        let ascent = font.as_scaled(PxScale::from(line.size)).ascent();
+        // Right-aligned in the right-hand corners, like the rest of the desktop's corner text
+        let x = match overlay.position {
+            OverlayPosition::TopRight | OverlayPosition::BottomRight => {
+                left + block_width - text_width(font, line.size, &line.text)
+            }
+            _ => left,
+        };
+        draw_text(image, font, line.size, &line.text, (x, y + ascent), opacity);
+        y += line_height(line);
+    }
+}
+
+/// The caption font, loaded on first use (see [`system_font`]).
+static FONT: OnceLock<Option<FontVec>> = OnceLock::new();
+
+/// The desktop's sans-serif font, loaded once.
+fn system_font() -> Option<&'static FontVec> {
+    FONT.get_or_init(|| {
+        font_candidates()
+            .into_iter()
+            .find_map(|path| FontVec::try_from_vec(std::fs::read(path).ok()?).ok())
+    })
+    .as_ref()
+}
+
+/// Font files to try, best first: fontconfig's sans-serif pick, then the fallbacks.
+fn font_candidates() -> Vec<PathBuf> {
+    let mut candidates = Vec::new();
+    if let Ok(output) = std::process::Command::new("fc-match").args(["-f", "%{file}", "sans-serif"]).output() {
+        let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if output.status.success() && !path.is_empty() {
+            candidates.push(PathBuf::from(path));
+        }
+    }
+    candidates.extend(FALLBACK_FONTS.iter().map(PathBuf::from));
+    candidates
+}
+
+/// Width of `text` in pixels at `size`, including kerning.
+fn text_width(font: &FontVec, size: f32, text: &str) -> f32 {
+    let scaled = font.as_scaled(PxScale::from(size));
+    let mut width = 0.0;
+    let mut previous = None;
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(previous) = previous {
+            width += scaled.kern(previous, id);
+        }
+        width += scaled.h_advance(id);
+        previous = Some(id);
+    }
+    width
+}
+
+/// Splits `text` into lines no wider than `max_width` at word boundaries.
+/// A single word wider than that gets a line of its own.
+fn wrap(font: &FontVec, size: f32, text: &str, max_width: f32) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        let candidate = if current.is_empty() { word.to_string() } else { format!("{current} {word}") };
+        if current.is_empty() || text_width(font, size, &candidate) <= max_width {
+            current = candidate;
+        } else {
+            lines.push(std::mem::replace(&mut current, word.to_string()));
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+/// Draws one line of white text with its baseline starting at `origin`.
+fn draw_text(image: &mut image::RgbImage, font: &FontVec, size: f32, text: &str, origin: (f32, f32), opacity: f32) {
+    let scale = PxScale::from(size);
+    let scaled = font.as_scaled(scale);
+    let (width, height) = image.dimensions();
+    let mut caret = origin.0;
+    let mut previous = None;
+
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(previous) = previous {
+            caret += scaled.kern(previous, id);
+        }
+        let glyph = id.with_scale_and_position(scale, point(caret, origin.1));
+        caret += scaled.h_advance(id);
+        previous = Some(id);
+
+        let Some(outlined) = font.outline_glyph(glyph) else {
+            continue; // e.g., a space
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|x, y, coverage| {
+            let x = bounds.min.x as i64 + x as i64;
+            let y = bounds.min.y as i64 + y as i64;
+            if (0..width as i64).contains(&x) && (0..height as i64).contains(&y) {
+                blend(image.get_pixel_mut(x as u32, y as u32), [255.0; 3], coverage * opacity);
+            }
+        });
+    }
+}
+
+/// Darkens the rectangle between `min` and `max` (clamped to the image) by `alpha`.
+fn fill_rect(image: &mut image::RgbImage, min: (f32, f32), max: (f32, f32), alpha: f32) {
+    let (width, height) = image.dimensions();
+    let x_range = (min.0.max(0.0) as u32)..(max.0.max(0.0) as u32).min(width);
+    let y_range = (min.1.max(0.0) as u32)..(max.1.max(0.0) as u32).min(height);
+    for y in y_range {
+        for x in x_range.clone() {
+            blend(image.get_pixel_mut(x, y), [0.0; 3], alpha);
+        }
+    }
+}
+
+/// Mixes `color` into `pixel` with the given opacity (0-1).
+fn blend(pixel: &mut image::Rgb<u8>, color: [f32; 3], alpha: f32) {
+    let alpha = alpha.clamp(0.0, 1.0);
+    for (channel, color) in pixel.0.iter_mut().zip(color) {
+        *channel = (*channel as f32 * (1.0 - alpha) + color * alpha).round() as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info() -> ImageMetadata {
+        ImageMetadata {
+            title: "Northern lights over Lapland".to_string(),
+            copyright: "© Photographer".to_string(),
+            start_date: "20260205".to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Average red value of a region
+    fn brightness(image: &image::RgbImage, xs: std::ops::Range<u32>, ys: std::ops::Range<u32>) -> f32 {
+        let pixels: Vec<f32> = ys
+            .flat_map(|y| xs.clone().map(move |x| (x, y)))
+            .map(|(x, y)| image.get_pixel(x, y).0[0] as f32)
+            .collect();
+        pixels.iter().sum::<f32>() / pixels.len() as f32
+    }
+
+    /// The bundled test font, set as the caption font so the tests don't
+    /// depend on the fonts installed (unless a test already loaded one).
+    fn font() -> &'static FontVec {
+        let bundled = include_bytes!("../tests/fonts/DejaVuSansMono.ttf");
+        let _ = FONT.set(FontVec::try_from_vec(bundled.to_vec()).ok());
+        system_font().expect("bundled test font")
+    }
+
+    #[test]
+    fn test_caption_is_drawn_in_the_chosen_corner() {
+        let font = font();
+        let overlay = Overlay { enabled: true, position: OverlayPosition::BottomLeft, scrim: 60, ..Overlay::default() };
+        let mut image = image::RgbImage::from_pixel(960, 540, image::Rgb([128, 128, 128]));
+        draw_caption_with(&mut image, font, &info(), &overlay);
+
+        // The bottom-left corner has the scrim and the text; the top right is untouched
+        let corner = brightness(&image, 20..380, 450..520);
+        assert!(corner != 128.0, "caption missing");
+        assert_eq!(brightness(&image, 600..960, 0..200), 128.0);
+
+        // Text is lighter than the scrim around it
+        let scrim_only = (128.0_f32 * 0.4).round();
+        assert!(image.pixels().any(|p| p.0[0] as f32 > scrim_only + 50.0));
+    }
+
+    #[test]
+    fn test_caption_on_sample_jpeg() {
+        font();
+        let dir = std::env::temp_dir().join(format!("bing-wallpaper-overlay-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let wallpaper_dir = dir.to_str().unwrap();
+
+        let original = dir.join("bing-en-US-2026-02-05.jpg");
+        crate::process::save_jpeg(&image::RgbImage::from_pixel(1280, 720, image::Rgb([90, 140, 200])), &original).unwrap();
+        let original_bytes = std::fs::read(&original).unwrap();
+        crate::metadata::record(wallpaper_dir, ImageMetadata { filename: "bing-en-US-2026-02-05.jpg".to_string(), ..info() }).unwrap();
+
+        let config = crate::config::Config {
+            wallpaper_dir: wallpaper_dir.to_string(),
+            overlay: Overlay { enabled: true, ..Overlay::default() },
+            ..Default::default()
+        };
+        let applied = crate::process::prepare(original.to_str().unwrap(), &config);
+        assert_ne!(applied, original.to_str().unwrap());
+
+        // The copy has the caption in the top right; the original is untouched
+        let copy = image::open(&applied).unwrap().to_rgb8();
+        assert_eq!(copy.dimensions(), (1280, 720));
+        let changed = (1100..1250).flat_map(|x| (30..60).map(move |y| (x, y)))
+            .filter(|&(x, y)| copy.get_pixel(x, y).0[0].abs_diff(90) > 10)
+            .count();
+        assert!(changed > 150 * 30 / 2, "caption missing");
+        assert!((brightness(&copy, 0..400, 500..720) - 90.0).abs() < 3.0);
+        assert_eq!(std::fs::read(&original).unwrap(), original_bytes);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_wrap_long_lines() {
+        let font = font();
+        let text = "A very long title that cannot possibly fit on one line of a small image";
+        let lines = wrap(font, 20.0, text, 200.0);
+        assert!(lines.len() > 2);
+        assert_eq!(lines.join(" "), text);
+        assert!(lines.iter().all(|l| text_width(font, 20.0, l) <= 200.0 || !l.contains(' ')));
+        assert!(wrap(font, 20.0, "", 200.0).is_empty());
+    }
+}
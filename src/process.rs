@@ -0,0 +1,428 @@
+//! # Image Post-Processing
+//!
+//! Optionally edits a copy of the wallpaper before it is applied, e.g. to get
+//! a dimmed, slightly blurred background that stays out of the way of busy
+//! windows. The steps come from [`Config::processing`] and always run in
+//! this order:
+//!
+//! 1. Crop to an aspect ratio (centered), e.g. `21:9` for an ultrawide screen
+//! 2. Gaussian blur
+//! 3. Darken or brighten
+//! 4. Desaturate
+//! 5. Vignette (darkened edges)
+//!
+//! While the COSMIC theme is dark, the [dark variant](crate::theme) then
+//! darkens the result further. The [caption overlay](crate::overlay), if
+//! enabled, is drawn last, so the text isn't blurred or dimmed along with the
+//! photo.
+//!
+//! ## Caching
+//! The downloaded original is never changed, so history keeps showing (and
+//! applying) the real photo. Processed copies are cached as JPEGs in
+//! `<wallpaper_dir>/.processed/`, hidden like the metadata index so photo
+//! libraries skip them. A copy's name ends in a hash of the settings that
+//! made it (processing, overlay, caption language and, for the dark variant,
+//! its dimming), so changing a setting produces a new copy instead of reusing
+//! a stale one. The light and dark copies are kept side by side so a theme
+//! switch is instant; copies made with other settings are removed by
+//! [`prune_cache`] during cleanup.
+
+use std::path::{Path, PathBuf};
+
+use crate::config::{Config, Overlay, Processing};
+use crate::error::Error;
+use crate::metadata;
+
+/// Directory inside the wallpaper directory that holds processed copies.
+pub const CACHE_DIRNAME: &str = ".processed";
+
+/// JPEG quality of processed copies.
+const JPEG_QUALITY: u8 = 92;
+
+/// Returns the image to set as the wallpaper: `image_path` itself, or its
+/// processed copy when processing, the caption overlay or (with a dark
+/// theme) the dark variant is enabled.
+///
+/// The copy is made on first use and cached. If processing fails, the
+/// original is used so the wallpaper still changes.
+pub fn prepare(image_path: &str, config: &Config) -> String {
+    let dark = config.dark_variant.is_active() && crate::theme::is_dark();
+    if !is_active(config, dark) {
+        return image_path.to_string();
+    }
+
+    let cached = cache_path(config, image_path, dark);
+    if cached.exists() {
+        // Counts as used, so cleanup keeps it for as long as it's being applied
+        let _ = std::fs::File::options()
+            .append(true)
+            .open(&cached)
+            .and_then(|file| file.set_modified(std::time::SystemTime::now()));
+        return cached.to_string_lossy().to_string();
+    }
+
+    match process_file(image_path, config, dark) {
+        Ok(path) => path,
+        Err(e) => {
+            eprintln!("Failed to process {image_path}, using the original: {e}");
+            image_path.to_string()
+        }
+    }
+}
+
+/// Whether the wallpaper is set from a derived copy rather than the original
+/// (`dark`: whether the dark variant applies).
+fn is_active(config: &Config, dark: bool) -> bool {
+    config.processing.is_active() || config.overlay.enabled || dark
+}
+
+/// Short hash of the settings that shape a derived copy, used to name it.
+fn cache_key(config: &Config, dark: bool) -> String {
+    let mut settings = serde_json::to_vec(&config.processing).unwrap_or_default();
+    if config.overlay.enabled {
+        settings.extend(serde_json::to_vec(&config.overlay).unwrap_or_default());
+        settings.extend(config.caption_market.as_bytes());
+    }
+    if dark {
+        settings.extend(serde_json::to_vec(&config.dark_variant).unwrap_or_default());
+    }
+    metadata::content_hash(&settings)[..12].to_string()
+}
+
+/// Where the derived copy of `image_path` for the current settings is cached:
+/// `.processed/{stem}-{settings hash}.jpg` (see [`cache_stem`]).
+fn cache_path(config: &Config, image_path: &str, dark: bool) -> PathBuf {
+    let stem = cache_stem(config, image_path);
+    Path::new(&config.wallpaper_dir).join(CACHE_DIRNAME).join(format!("{stem}-{}.jpg", cache_key(config, dark)))
+}
+
+/// The part of a cached copy's name that identifies the original: its path
+/// relative to the wallpaper directory without the extension, with `/`
+/// replaced by `_` (images may live in date subdirectories).
+fn cache_stem(config: &Config, image_path: &str) -> String {
+    let path = Path::new(image_path);
+    let relative = path.strip_prefix(&config.wallpaper_dir).unwrap_or(path);
+    let stem = relative.with_extension("");
+    stem.to_string_lossy().trim_start_matches('/').replace('/', "_")
+}
+
+/// Finds the image a wallpaper was made from, given the path COSMIC shows:
+/// `applied` itself, or for a cached copy, the stored image it was made
+/// from. None if that image isn't in the wallpaper directory any more.
+pub fn original_path(config: &Config, applied: &str) -> Option<String> {
+    let cache_dir = Path::new(&config.wallpaper_dir).join(CACHE_DIRNAME);
+    let Ok(name) = Path::new(applied).strip_prefix(&cache_dir) else {
+        return Some(applied.to_string());
+    };
+    let (stem, _hash) = name.to_str()?.strip_suffix(".jpg")?.rsplit_once('-')?;
+    metadata::stored_images(&config.wallpaper_dir)
+        .into_iter()
+        .map(|(path, _)| path.to_string_lossy().to_string())
+        .find(|path| cache_stem(config, path) == stem)
+}
+
+/// Like [`original_path`], but only for wallpapers this app set: a cached
+/// copy, or an image inside the wallpaper directory that has an index entry.
+/// None for anything else, e.g. a wallpaper picked in COSMIC Settings.
+pub fn managed_original(config: &Config, applied: &str) -> Option<String> {
+    let path = Path::new(applied);
+    if path.starts_with(Path::new(&config.wallpaper_dir).join(CACHE_DIRNAME)) {
+        original_path(config, applied)
+    } else if path.starts_with(&config.wallpaper_dir) && metadata::lookup(applied).is_some() {
+        Some(applied.to_string())
+    } else {
+        None
+    }
+}
+
+/// Loads `source`, applies the steps, the dark variant (if `dark`) and the
+/// caption overlay, and caches the result as a JPEG.
+///
+/// A missing caption (no font, or no metadata for the image) shouldn't cost
+/// the user the rest of the processing, but the copy isn't cached under the
+/// overlay's settings either: it goes where a copy without the overlay
+/// belongs, so the caption is tried again next time. With nothing else to
+/// apply, that is the original itself.
+///
+/// # Returns
+/// * `Ok(String)` - Path of the image to set as the wallpaper
+/// * `Err(Error)` - The image couldn't be read or the copy couldn't be written
+fn process_file(source: &str, config: &Config, dark: bool) -> Result<String, Error> {
+    let mut image = image::open(source)
+        .map_err(|e| Error::InvalidResponse(format!("Failed to read image: {e}")))?
+        .to_rgb8();
+    if config.processing.is_active() {
+        image = apply_steps(image, &config.processing);
+    }
+    if dark {
+        let dim = Processing { brightness: -(config.dark_variant.dim.min(100) as i32), ..Processing::default() };
+        adjust_pixels(&mut image, &dim);
+    }
+
+    let captioned = !config.overlay.enabled || match metadata::lookup(source) {
+        Some(info) => crate::overlay::draw_caption(&mut image, &info, &config.overlay)
+            .map_err(|e| eprintln!("Failed to draw the caption: {e}"))
+            .is_ok(),
+        None => {
+            eprintln!("No metadata for {source}, leaving out the caption");
+            false
+        }
+    };
+
+    let dest = if captioned {
+        cache_path(config, source, dark)
+    } else {
+        let without_caption = Config { overlay: Overlay { enabled: false, ..config.overlay.clone() }, ..config.clone() };
+        if !is_active(&without_caption, dark) {
+            return Ok(source.to_string());
+        }
+        cache_path(&without_caption, source, dark)
+    };
+    save_jpeg(&image, &dest)?;
+    Ok(dest.to_string_lossy().to_string())
+}
+
+/// Writes an image as a JPEG, via a temporary file so a crash can't leave a
+/// half-written copy in the cache.
+pub fn save_jpeg(image: &image::RgbImage, dest: &Path) -> Result<(), Error> {
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| Error::Io(format!("Failed to create cache directory: {e}")))?;
+    }
+
+    let mut bytes = Vec::new();
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut bytes, JPEG_QUALITY)
+        .encode_image(image)
+        .map_err(|e| Error::Io(format!("Failed to encode image: {e}")))?;
+
+    let tmp = dest.with_extension("jpg.tmp");
+    std::fs::write(&tmp, bytes).map_err(|e| Error::Io(format!("Failed to write image: {e}")))?;
+    std::fs::rename(&tmp, dest).map_err(|e| Error::Io(format!("Failed to write image: {e}")))
+}
+
+/// Runs every enabled step, in the order listed in the [module docs](self).
+pub fn apply_steps(image: image::RgbImage, processing: &Processing) -> image::RgbImage {
+    let mut image = match crop_aspect(&processing.crop_aspect) {
+        Some(aspect) => crop_to_aspect(image, aspect),
+        None => image,
+    };
+    if processing.blur > 0 {
+        image = image::imageops::blur(&image, processing.blur as f32);
+    }
+    if processing.brightness != 0 || processing.desaturate > 0 || processing.vignette > 0 {
+        adjust_pixels(&mut image, processing);
+    }
+    image
+}
+
+/// Parses an aspect ratio setting: "W:H" (e.g., "16:9"), or "display" for
+/// the largest connected display's. None if empty or invalid.
+fn crop_aspect(setting: &str) -> Option<f64> {
+    let setting = setting.trim();
+    if setting.eq_ignore_ascii_case("display") {
+        let display = crate::display::connected_displays()
+            .into_iter()
+            .max_by_key(|d| d.width * d.height)?;
+        return Some(display.width as f64 / display.height as f64);
+    }
+    let (w, h) = setting.split_once(':')?;
+    let (w, h): (f64, f64) = (w.trim().parse().ok()?, h.trim().parse().ok()?);
+    (w > 0.0 && h > 0.0).then_some(w / h)
+}
+
+/// Crops the largest centered region with the given width/height ratio.
+fn crop_to_aspect(image: image::RgbImage, aspect: f64) -> image::RgbImage {
+    let (width, height) = image.dimensions();
+    let (crop_w, crop_h) = if width as f64 / height as f64 > aspect {
+        (((height as f64 * aspect).round() as u32).clamp(1, width), height)
+    } else {
+        (width, ((width as f64 / aspect).round() as u32).clamp(1, height))
+    };
+    if (crop_w, crop_h) == (width, height) {
+        return image;
+    }
+    image::imageops::crop_imm(&image, (width - crop_w) / 2, (height - crop_h) / 2, crop_w, crop_h).to_image()
+}
+
+/// Brightness, desaturation and vignette, done together in one pass over the pixels.
+fn adjust_pixels(image: &mut image::RgbImage, processing: &Processing) {
+    let brightness = processing.brightness.clamp(-100, 100) as f32 / 100.0;
+    let desaturate = processing.desaturate.min(100) as f32 / 100.0;
+    let vignette = processing.vignette.min(100) as f32 / 100.0;
+    let (width, height) = image.dimensions();
+    let (center_x, center_y) = (width as f32 / 2.0, height as f32 / 2.0);
+
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        let [r, g, b] = pixel.0.map(|c| c as f32);
+
+        // Desaturate: blend towards the pixel's luma
+        let luma = 0.299 * r + 0.587 * g + 0.114 * b;
+        let mut rgb = [r, g, b].map(|c| c + (luma - c) * desaturate);
+
+        // Darken towards black, or brighten towards white
+        rgb = rgb.map(|c| if brightness < 0.0 { c * (1.0 + brightness) } else { c + (255.0 - c) * brightness });
+
+        // Vignette: darker with the squared distance from the center (1 at the corners)
+        if vignette > 0.0 {
+            let dx = (x as f32 - center_x) / center_x;
+            let dy = (y as f32 - center_y) / center_y;
+            let distance = (dx * dx + dy * dy) / 2.0;
+            rgb = rgb.map(|c| c * (1.0 - vignette * distance));
+        }
+
+        pixel.0 = rgb.map(|c| c.round().clamp(0.0, 255.0) as u8);
+    }
+}
+
+/// Removes cached copies in `wallpaper_dir` that weren't made with the
+/// current settings in `config` (all of them when nothing is enabled) or
+/// weren't used for `keep_days` (0 = no age limit). Both the light and the
+/// dark copy count as current. Returns how many were removed.
+pub fn prune_cache(wallpaper_dir: &str, config: &Config, keep_days: u32) -> usize {
+    let cache_dir = Path::new(wallpaper_dir).join(CACHE_DIRNAME);
+    let Ok(entries) = std::fs::read_dir(&cache_dir) else {
+        return 0;
+    };
+
+    let suffixes: Vec<String> = [false, true]
+        .into_iter()
+        .filter(|&dark| if dark { config.dark_variant.is_active() } else { is_active(config, false) })
+        .map(|dark| format!("-{}.jpg", cache_key(config, dark)))
+        .collect();
+    let max_age = std::time::Duration::from_secs(keep_days as u64 * 24 * 60 * 60);
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let current = suffixes.iter().any(|suffix| name.ends_with(suffix.as_str()));
+        let expired = keep_days > 0
+            && entry.metadata()
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|modified| modified.elapsed().ok())
+                .is_some_and(|age| age > max_age);
+        if (!current || expired) && std::fs::remove_file(entry.path()).is_ok() {
+            removed += 1;
+        }
+    }
+    // Drop the directory too once it's empty
+    let _ = std::fs::remove_dir(&cache_dir);
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray(width: u32, height: u32, value: u8) -> image::RgbImage {
+        image::RgbImage::from_pixel(width, height, image::Rgb([value, value, value]))
+    }
+
+    #[test]
+    fn test_crop_to_aspect() {
+        assert_eq!(crop_aspect("21:9"), Some(21.0 / 9.0));
+        assert_eq!(crop_aspect(""), None);
+        assert_eq!(crop_aspect("16:0"), None);
+
+        assert_eq!(crop_to_aspect(gray(1920, 1080, 0), 1.0).dimensions(), (1080, 1080));
+        assert_eq!(crop_to_aspect(gray(1920, 1080, 0), 21.0 / 9.0).dimensions(), (1920, 823));
+        assert_eq!(crop_to_aspect(gray(1920, 1080, 0), 16.0 / 9.0).dimensions(), (1920, 1080));
+    }
+
+    #[test]
+    fn test_pixel_adjustments() {
+        let darken = Processing { enabled: true, brightness: -50, ..Processing::default() };
+        assert_eq!(apply_steps(gray(4, 4, 200), &darken).get_pixel(0, 0).0, [100, 100, 100]);
+
+        let brighten = Processing { enabled: true, brightness: 50, ..Processing::default() };
+        assert_eq!(apply_steps(gray(4, 4, 55), &brighten).get_pixel(0, 0).0, [155, 155, 155]);
+
+        let desaturate = Processing { enabled: true, desaturate: 100, ..Processing::default() };
+        let red = image::RgbImage::from_pixel(4, 4, image::Rgb([255, 0, 0]));
+        let [r, g, b] = apply_steps(red, &desaturate).get_pixel(0, 0).0;
+        assert!(r == g && g == b);
+
+        // The vignette darkens the corners, not the center
+        let vignette = Processing { enabled: true, vignette: 100, ..Processing::default() };
+        let image = apply_steps(gray(101, 101, 200), &vignette);
+        assert_eq!(image.get_pixel(50, 50).0[0], 200);
+        assert!(image.get_pixel(0, 0).0[0] < 10);
+    }
+
+    #[test]
+    fn test_cache_key_follows_settings() {
+        let config = Config {
+            wallpaper_dir: "/pictures".to_string(),
+            processing: Processing { enabled: true, blur: 4, ..Processing::default() },
+            ..Config::default()
+        };
+        let first = cache_path(&config, "/pictures/2026/02/fox.jpg", false);
+        assert!(first.to_string_lossy().starts_with("/pictures/.processed/2026_02_fox-"));
+
+        let changed = Config { processing: Processing { blur: 8, ..config.processing.clone() }, ..config.clone() };
+        assert_ne!(first, cache_path(&changed, "/pictures/2026/02/fox.jpg", false));
+        assert_ne!(first, cache_path(&config, "/pictures/2026/02/fox.jpg", true));
+        assert!(!is_active(&Config::default(), false));
+        assert!(is_active(&Config::default(), true));
+    }
+
+    #[test]
+    fn test_copy_without_caption_is_not_cached_as_captioned() {
+        let dir = std::env::temp_dir().join(format!("bing-wallpaper-uncaptioned-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        // No metadata is recorded, so there is no caption to draw
+        let original = dir.join("bing-en-US-2026-02-05.jpg");
+        save_jpeg(&gray(8, 8, 100), &original).unwrap();
+        let original = original.to_string_lossy().to_string();
+
+        let overlay_only = Config {
+            wallpaper_dir: dir.to_string_lossy().to_string(),
+            overlay: Overlay { enabled: true, ..Overlay::default() },
+            ..Config::default()
+        };
+        assert_eq!(prepare(&original, &overlay_only), original);
+        assert!(!dir.join(CACHE_DIRNAME).exists());
+
+        // The blur still applies, cached as if the overlay were off
+        let blurred = Config {
+            processing: Processing { enabled: true, blur: 2, ..Processing::default() },
+            ..overlay_only.clone()
+        };
+        let without_caption = Config { overlay: Overlay::default(), ..blurred.clone() };
+        let applied = prepare(&original, &blurred);
+        assert_eq!(applied, cache_path(&without_caption, &original, false).to_string_lossy());
+        assert!(!cache_path(&blurred, &original, false).exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_original_path_of_cached_copy() {
+        let dir = std::env::temp_dir().join(format!("bing-wallpaper-process-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("2026/02")).unwrap();
+        let original = dir.join("2026/02/bing-en-US-2026-02-05.jpg");
+        save_jpeg(&gray(8, 8, 100), &original).unwrap();
+        metadata::record(dir.to_str().unwrap(), metadata::ImageMetadata {
+            filename: "2026/02/bing-en-US-2026-02-05.jpg".to_string(),
+            ..Default::default()
+        }).unwrap();
+
+        let config = Config { wallpaper_dir: dir.to_string_lossy().to_string(), ..Config::default() };
+        let original = original.to_string_lossy().to_string();
+        let dark_copy = cache_path(&config, &original, true);
+        assert_eq!(original_path(&config, &dark_copy.to_string_lossy()), Some(original.clone()));
+        assert_eq!(original_path(&config, &original), Some(original.clone()));
+        assert_eq!(original_path(&config, &dir.join(".processed/gone-0123456789ab.jpg").to_string_lossy()), None);
+
+        // Only our own wallpapers are adopted, not one set in COSMIC Settings
+        assert_eq!(managed_original(&config, &dark_copy.to_string_lossy()), Some(original.clone()));
+        assert_eq!(managed_original(&config, &original), Some(original.clone()));
+        let unindexed = dir.join("holiday.jpg");
+        save_jpeg(&gray(8, 8, 100), &unindexed).unwrap();
+        assert_eq!(managed_original(&config, &unindexed.to_string_lossy()), None);
+        assert_eq!(managed_original(&config, "/usr/share/backgrounds/cosmic/orion.jpg"), None);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,162 @@
+//! # Retries
+//!
+//! Retries a fetch according to the [`RetryPolicy`] in the config. Every
+//! fetch path uses it: the applet's Fetch button, the daily timer, the D-Bus
+//! `FetchWallpaper` and `BackfillArchive` methods, `--fetch`, `--backfill`
+//! and the images for [per-display wallpapers](crate::outputs). Network hiccups are common right
+//! after resume from suspend, which is exactly when the timer's catch-up
+//! fetch runs.
+//!
+//! Only transient errors are retried (see [`Error::is_transient`]); a 404 or
+//! an invalid image fails straight away. Before each retry the caller is told
+//! what failed and how long the wait is, so it can show it to the user.
+
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use crate::config::RetryPolicy;
+use crate::error::Error;
+
+/// A failed attempt that is about to be retried.
+#[derive(Debug, Clone)]
+pub struct Retry {
+    /// Number of the upcoming attempt (2 for the first retry)
+    pub attempt: u32,
+    /// Total number of attempts allowed by the policy
+    pub attempts: u32,
+    /// How long we wait before the upcoming attempt
+    pub delay: Duration,
+    /// What went wrong with the previous attempt
+    pub error: Error,
+}
+
+impl Retry {
+    /// Status text, e.g. "Request timed out - retrying in 12s (attempt 2 of 3)".
+    pub fn message(&self) -> String {
+        format!(
+            "{} - retrying in {}s (attempt {} of {})",
+            self.error,
+            self.delay.as_secs(),
+            self.attempt,
+            self.attempts,
+        )
+    }
+}
+
+/// The delay before retry number `retry` (1 for the first retry), without jitter.
+///
+/// Doubles each time: `base`, `2 × base`, `4 × base`, ... up to `max_delay_secs`.
+pub fn backoff(policy: &RetryPolicy, retry: u32) -> Duration {
+    let factor = 1u64.checked_shl(retry.saturating_sub(1)).unwrap_or(u64::MAX);
+    let secs = policy.base_delay_secs.saturating_mul(factor).min(policy.max_delay_secs);
+    Duration::from_secs(secs)
+}
+
+/// Randomly moves `delay` by up to `percent` percent in either direction.
+fn jitter(delay: Duration, percent: u32) -> Duration {
+    let percent = percent.min(100) as u64;
+    let spread = delay.as_millis() as u64 * percent / 100;
+    if spread == 0 {
+        return delay;
+    }
+
+    // The standard library's hasher is randomly seeded per instance, which
+    // is plenty of randomness for spreading out retries
+    let random = std::collections::hash_map::RandomState::new().build_hasher().finish();
+    let offset = random % (2 * spread + 1);
+    Duration::from_millis(delay.as_millis() as u64 - spread + offset)
+}
+
+/// Runs `operation` until it succeeds, fails with a permanent error, or the
+/// policy's attempts are used up.
+///
+/// `on_retry` is called before each wait with the error and the delay.
+///
+/// # Returns
+/// * `Ok(T)` - The first successful result
+/// * `Err(Error)` - The error from the last attempt
+pub async fn retry<T, F, Fut>(
+    policy: &RetryPolicy,
+    mut operation: F,
+    on_retry: impl Fn(&Retry),
+) -> Result<T, Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Error>>,
+{
+    let attempts = policy.attempts.max(1);
+    let mut attempt = 1;
+    loop {
+        match operation().await {
+            Ok(value) => return Ok(value),
+            Err(error) if error.is_transient() && attempt < attempts => {
+                let delay = jitter(backoff(policy, attempt), policy.jitter_percent);
+                attempt += 1;
+                on_retry(&Retry { attempt, attempts, delay, error });
+                tokio::time::sleep(delay).await;
+            }
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[test]
+    fn test_backoff_doubles_up_to_max() {
+        let policy = RetryPolicy { attempts: 10, base_delay_secs: 10, max_delay_secs: 60, jitter_percent: 0 };
+        let delays: Vec<u64> = (1..=5).map(|n| backoff(&policy, n).as_secs()).collect();
+        assert_eq!(delays, vec![10, 20, 40, 60, 60]);
+        assert_eq!(backoff(&policy, 100).as_secs(), 60);
+
+        for _ in 0..100 {
+            let delay = jitter(Duration::from_secs(10), 20);
+            assert!(delay >= Duration::from_secs(8) && delay <= Duration::from_secs(12));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retries_only_transient_errors() {
+        let policy = RetryPolicy { attempts: 3, base_delay_secs: 0, max_delay_secs: 0, jitter_percent: 0 };
+
+        // Transient: tried `attempts` times, reporting each retry
+        let calls = AtomicU32::new(0);
+        let retries = AtomicU32::new(0);
+        let result: Result<(), Error> = retry(
+            &policy,
+            || async { calls.fetch_add(1, Ordering::SeqCst); Err(Error::Timeout("timed out".to_string())) },
+            |r| { retries.fetch_add(1, Ordering::SeqCst); assert_eq!(r.attempts, 3); },
+        ).await;
+        assert_eq!(result, Err(Error::Timeout("timed out".to_string())));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert_eq!(retries.load(Ordering::SeqCst), 2);
+
+        // Permanent: tried once
+        let calls = AtomicU32::new(0);
+        let result: Result<(), Error> = retry(
+            &policy,
+            || async { calls.fetch_add(1, Ordering::SeqCst); Err(Error::InvalidResponse("bad".to_string())) },
+            |_| panic!("permanent errors must not be retried"),
+        ).await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Succeeds on the second try
+        let calls = AtomicU32::new(0);
+        let result = retry(
+            &policy,
+            || async {
+                match calls.fetch_add(1, Ordering::SeqCst) {
+                    0 => Err(Error::Network("reset".to_string())),
+                    _ => Ok("done"),
+                }
+            },
+            |_| {},
+        ).await;
+        assert_eq!(result, Ok("done"));
+    }
+}
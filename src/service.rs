@@ -10,9 +10,12 @@
 //!
 //! ### Methods
 //! - `FetchWallpaper(apply: bool)` - Fetch today's wallpaper, optionally apply it
+//! - `BackfillArchive()` - Download any of the last 8 days missing from the wallpaper directory
 //! - `ApplyWallpaper(path: String)` - Apply a specific wallpaper by path
+//! - `RevertWallpaper()` - Go back to the wallpaper shown before the last change
 //! - `GetConfig()` - Get current configuration
-//! - `SetMarket(market: String)` - Set the Bing regional market
+//! - `SetMarket(market: String)` - Set the Bing regional market (must be known or a custom market)
+//! - `AddCustomMarket(market: String)` - Check a market code against the Bing API and save it as a custom market
 //! - `GetTimerEnabled()` - Check if auto-update timer is enabled
 //! - `SetTimerEnabled(enabled: bool)` - Enable or disable auto-update timer
 //! - `GetHistory()` - Get list of downloaded wallpapers
@@ -21,6 +24,7 @@
 //! - `WallpaperChanged(path: String, title: String)` - Emitted when wallpaper changes
 //! - `TimerStateChanged(enabled: bool)` - Emitted when timer state changes
 //! - `FetchProgress(state: String, message: String)` - Emitted during fetch operations
+//!   (states: `starting`, `downloading`, `retrying`, `applying`, `complete`)
 
 // --- Standard library and async imports ---
 use std::future::Future;    // Trait for async functions (used by run_in_tokio)
@@ -31,8 +35,15 @@ use tokio::sync::RwLock;    // Async read-write lock for shared state
 use zbus::{interface, SignalContext};  // interface = attribute macro, SignalContext = for emitting signals
 
 // --- Internal modules ---
-use crate::bing::{self, BingImage};   // Bing API client
+use crate::background::{self, Snapshot}; // COSMIC background config (cosmic-bg)
+use crate::bing;                      // Bing API client (archive backfill)
 use crate::config::Config;           // User configuration
+use crate::error::Error;             // Typed errors (mapped to D-Bus error names)
+use crate::http::NetworkSettings;    // Proxy and certificates for requests
+use crate::metadata::{self, ImageMetadata};  // Per-image metadata index
+use crate::network;                  // NetworkManager state (metered policy)
+use crate::retry;                    // Shared retry/backoff policy
+use crate::source;                   // Pluggable wallpaper sources
 use crate::timer::InternalTimer;     // Daily timer
 
 /// Checks if the application is running inside a Flatpak sandbox.
@@ -49,7 +60,8 @@ pub fn is_flatpak() -> bool {
 /// The D-Bus service methods are called by zbus's own async executor,
 /// which is NOT tokio. But our HTTP client (reqwest) requires tokio.
 /// This helper creates a temporary single-threaded tokio runtime to
-/// bridge the gap. Each D-Bus method call gets its own mini-runtime.
+/// bridge the gap. It blocks the calling thread, so D-Bus methods use it
+/// through [`in_tokio_thread`].
 fn run_in_tokio<T>(future: impl Future<Output = T>) -> T {
     let rt = tokio::runtime::Builder::new_current_thread()
         .enable_all()    // Enable I/O and time drivers
@@ -58,6 +70,18 @@ fn run_in_tokio<T>(future: impl Future<Output = T>) -> T {
     rt.block_on(future)
 }
 
+/// Runs the future `work` makes on a thread of its own, in its own tokio
+/// runtime (see [`run_in_tokio`]), and waits for it without blocking zbus's
+/// executor.
+///
+/// A fetch can spend minutes in retry waits; meanwhile other D-Bus calls are
+/// still answered and its progress signals still go out.
+async fn in_tokio_thread<T: Send + 'static, F: Future<Output = Result<T, Error>>>(
+    work: impl FnOnce() -> F + Send + 'static,
+) -> Result<T, Error> {
+    on_own_thread(move || run_in_tokio(work())).await
+}
+
 /// D-Bus service name — must be unique on the session bus.
 /// Other applications use this name to find and call our service.
 pub const SERVICE_NAME: &str = "io.github.reality2_roycdavies.cosmic-bing-wallpaper.Wallpaper1";
@@ -76,8 +100,53 @@ pub struct WallpaperInfo {
     pub path: String,
     /// Just the filename (e.g., "bing-en-US-2026-02-05.jpg")
     pub filename: String,
-    /// Date extracted from the filename (e.g., "2026-02-05")
+    /// Date the image was featured (e.g., "2026-02-05"), from the metadata
+    /// index or extracted from the filename
     pub date: String,
+    /// Image title from the metadata index (empty if unknown)
+    pub title: String,
+    /// Copyright/attribution text from the metadata index (empty if unknown)
+    pub copyright: String,
+    /// Link to more information about the image (empty if unknown)
+    pub copyright_link: String,
+}
+
+impl WallpaperInfo {
+    /// Builds a WallpaperInfo from a full image path, reading its details
+    /// from the metadata index.
+    pub fn from_path(path: String) -> Self {
+        let metadata = metadata::lookup(&path);
+        Self::with_metadata(path, metadata.as_ref())
+    }
+
+    /// Builds a WallpaperInfo from a full image path and its (optional) index entry.
+    /// Falls back to the date in the filename when there is no entry.
+    fn with_metadata(path: String, metadata: Option<&ImageMetadata>) -> Self {
+        let filename = std::path::Path::new(&path)
+            .file_name()
+            .and_then(|s| s.to_str())
+            .unwrap_or("")
+            .to_string();
+
+        match metadata {
+            Some(m) => Self {
+                date: m.display_date(),
+                title: m.display_title().to_string(),
+                copyright: m.display_copyright().to_string(),
+                copyright_link: m.copyright_link.clone(),
+                path,
+                filename,
+            },
+            None => Self {
+                date: extract_date_from_filename(&filename),
+                title: String::new(),
+                copyright: String::new(),
+                copyright_link: String::new(),
+                path,
+                filename,
+            },
+        }
+    }
 }
 
 /// Shared mutable state for the wallpaper service.
@@ -90,7 +159,7 @@ pub struct ServiceState {
     /// User configuration (market, wallpaper directory, keep_days, etc.)
     pub config: Config,
     /// Metadata for the most recently fetched image (for display/reference)
-    pub current_image: Option<BingImage>,
+    pub current_image: Option<ImageMetadata>,
     /// Filesystem path to the most recently applied wallpaper
     pub current_path: Option<String>,
     /// Reference to the internal timer (shared with the applet for enable/disable)
@@ -98,12 +167,20 @@ pub struct ServiceState {
 }
 
 impl ServiceState {
-    /// Creates a new ServiceState with default config loaded from disk
+    /// Creates a new ServiceState with default config loaded from disk.
+    ///
+    /// The current wallpaper is read back from COSMIC's background config, so
+    /// a theme switch right after login can re-apply it. Only a wallpaper this
+    /// app set is adopted (see [`crate::process::managed_original`]): one the
+    /// user picked in COSMIC Settings must not be dimmed or captioned.
     pub fn new(timer: Arc<InternalTimer>) -> Self {
+        let config = Config::load();
+        let current_path = background::applied_wallpaper()
+            .and_then(|path| crate::process::managed_original(&config, &path));
         Self {
-            config: Config::load(),
+            config,
             current_image: None,
-            current_path: None,
+            current_path,
             timer,
         }
     }
@@ -137,7 +214,7 @@ impl WallpaperService {
 /// when a method parameter is annotated with `#[zbus(signal_context)]`.
 #[interface(name = "io.github.reality2_roycdavies.cosmic_bing_wallpaper.Wallpaper1")]
 impl WallpaperService {
-    /// Fetch today's wallpaper from Bing
+    /// Fetch today's wallpaper from the configured source (Bing by default)
     ///
     /// # Arguments
     /// * `apply` - If true, also apply the wallpaper after downloading
@@ -153,27 +230,43 @@ impl WallpaperService {
         // Emit progress signal
         Self::fetch_progress(&ctx, "starting", "Fetching image info...").await?;
 
-        let (market, wallpaper_dir) = {
-            let state = self.state.read().await;
-            (state.config.market.clone(), state.config.wallpaper_dir.clone())
-        };
-
-        // Fetch image info from Bing (must run in tokio runtime since reqwest requires it)
-        let image = run_in_tokio(bing::fetch_bing_image_info(&market))
-            .map_err(zbus::fdo::Error::Failed)?;
-
-        Self::fetch_progress(&ctx, "downloading", &format!("Downloading: {}", image.title)).await?;
+        // Reload config from disk to pick up changes made in the settings window
+        // (the settings window writes config.json directly, not via D-Bus)
+        let config = Config::load();
+        {
+            let mut state = self.state.write().await;
+            state.config = config.clone();
+        }
 
-        // Download the image (must run in tokio runtime since reqwest requires it)
-        let path = run_in_tokio(bing::download_image(&image, &wallpaper_dir, &market))
-            .map_err(zbus::fdo::Error::Failed)?;
+        // Fetch and download, retrying transient failures (in a tokio runtime
+        // on its own thread, since reqwest requires tokio). Status updates
+        // (title, byte progress, retries) arrive from inside the fetch;
+        // forward them as signals while it runs
+        let (status_tx, mut status_rx) = tokio::sync::mpsc::unbounded_channel();
+        let fetch = {
+            let config = config.clone();
+            move || async move {
+                let network = network::status_for(config.metered).await;
+                let source = source::from_config(&config, network);
+                let on_retry = |r: &retry::Retry| {
+                    let _ = status_tx.send(("retrying", r.message()));
+                };
+                let fetched = retry::retry(
+                    &config.retry,
+                    || fetch_and_download(source.as_ref(), &config.wallpaper_dir, &status_tx),
+                    on_retry,
+                ).await?;
+                // Images for displays that show another market or day
+                crate::outputs::fetch_images(&config, network, on_retry).await;
+                Ok(fetched)
+            }
+        };
+        let (image, path) = forward_progress(&ctx, in_tokio_thread(fetch), &mut status_rx)
+            .await
+            .map_err(zbus::fdo::Error::from)?;
 
         // Clean up old wallpapers
-        let keep_days = {
-            let state = self.state.read().await;
-            state.config.keep_days
-        };
-        cleanup_old_wallpapers(&wallpaper_dir, keep_days);
+        cleanup_old_wallpapers(&config);
 
         // Update state
         {
@@ -186,10 +279,11 @@ impl WallpaperService {
         if apply {
             Self::fetch_progress(&ctx, "applying", "Applying wallpaper...").await?;
             apply_cosmic_wallpaper(&path)
-                .map_err(zbus::fdo::Error::Failed)?;
+                .await
+                .map_err(zbus::fdo::Error::from)?;
 
             // Emit wallpaper changed signal
-            Self::wallpaper_changed(&ctx, &path, &image.title).await?;
+            Self::wallpaper_changed(&ctx, &path, image.display_title()).await?;
         }
 
         // Record successful fetch for timer catch-up logic
@@ -200,15 +294,65 @@ impl WallpaperService {
 
         Self::fetch_progress(&ctx, "complete", "Done!").await?;
 
-        let filename = std::path::Path::new(&path)
-            .file_name()
-            .and_then(|s| s.to_str())
-            .unwrap_or("")
-            .to_string();
+        Ok(WallpaperInfo::from_path(path))
+    }
+
+    /// Download every day from Bing's archive that is missing locally
+    ///
+    /// Bing keeps about 8 days of images, so a machine that was offline
+    /// for a while can fill the gaps in its history with this call.
+    ///
+    /// Days that fail to download are skipped and listed in the final
+    /// `complete` progress message.
+    ///
+    /// # Returns
+    /// * Success: WallpaperInfo for each newly downloaded image (empty if none were missing)
+    /// * Error: Error message string (also when every missing day failed)
+    async fn backfill_archive(
+        &self,
+        #[zbus(signal_context)] ctx: SignalContext<'_>,
+    ) -> zbus::fdo::Result<Vec<WallpaperInfo>> {
+        Self::fetch_progress(&ctx, "starting", "Fetching archive...").await?;
+
+        // Reload config from disk, as for FetchWallpaper
+        let config = Config::load();
+        {
+            let mut state = self.state.write().await;
+            state.config = config.clone();
+        }
+        let market = config.todays_market();
+
+        // Fetch and download, retrying transient failures (in a tokio runtime
+        // on its own thread, since reqwest requires tokio); retries are
+        // forwarded as signals
+        let (status_tx, mut status_rx) = tokio::sync::mpsc::unbounded_channel();
+        let backfill = {
+            let config = config.clone();
+            move || async move {
+                let network = network::status_for(config.metered).await;
+                let settings = bing::ArchiveSettings::from_config(&config, network);
+                bing::backfill_archive(&settings, &market, |r| {
+                    let _ = status_tx.send(("retrying", r.message()));
+                }).await
+            }
+        };
+        let backfill = forward_progress(&ctx, in_tokio_thread(backfill), &mut status_rx)
+            .await
+            .map_err(zbus::fdo::Error::from)?;
+
+        cleanup_old_wallpapers(&config);
 
-        let date = extract_date_from_filename(&filename);
+        let summary = backfill.error_summary();
+        let message = match &summary {
+            Some(summary) if backfill.downloaded.is_empty() => {
+                return Err(zbus::fdo::Error::Failed(summary.clone()));
+            }
+            Some(summary) => format!("Downloaded {} missing day(s). {summary}", backfill.downloaded.len()),
+            None => format!("Downloaded {} missing day(s)", backfill.downloaded.len()),
+        };
+        Self::fetch_progress(&ctx, "complete", &message).await?;
 
-        Ok(WallpaperInfo { path, filename, date })
+        Ok(backfill.downloaded.into_iter().map(WallpaperInfo::from_path).collect())
     }
 
     /// Apply a specific wallpaper by path
@@ -218,27 +362,64 @@ impl WallpaperService {
         #[zbus(signal_context)] ctx: SignalContext<'_>,
     ) -> zbus::fdo::Result<()> {
         apply_cosmic_wallpaper(&path)
-            .map_err(zbus::fdo::Error::Failed)?;
+            .await
+            .map_err(zbus::fdo::Error::from)?;
 
-        // Get title from current image or use filename
+        // Get title from the metadata index, the current image, or the filename
         let title = {
-            let state = self.state.read().await;
-            state.current_image.as_ref()
-                .filter(|_| state.current_path.as_ref() == Some(&path))
-                .map(|img| img.title.clone())
+            let mut state = self.state.write().await;
+            let title = metadata::lookup(&path)
+                .map(|m| m.display_title().to_string())
+                .or_else(|| {
+                    state.current_image.as_ref()
+                        .filter(|_| state.current_path.as_ref() == Some(&path))
+                        .map(|img| img.display_title().to_string())
+                })
                 .unwrap_or_else(|| {
                     std::path::Path::new(&path)
                         .file_name()
                         .and_then(|s| s.to_str())
                         .unwrap_or("Wallpaper")
                         .to_string()
-                })
+                });
+            state.current_path = Some(path.clone());
+            title
         };
 
         Self::wallpaper_changed(&ctx, &path, &title).await?;
         Ok(())
     }
 
+    /// Go back to the wallpaper shown before the last change
+    ///
+    /// # Returns
+    /// * The path of the image now shown (empty if the previous background
+    ///   wasn't an image, e.g. a color set in COSMIC Settings)
+    /// * Error if there is nothing to go back to or applying it failed
+    async fn revert_wallpaper(
+        &self,
+        #[zbus(signal_context)] ctx: SignalContext<'_>,
+    ) -> zbus::fdo::Result<String> {
+        let path = revert_cosmic_wallpaper()
+            .await
+            .map_err(zbus::fdo::Error::from)?;
+
+        self.state.write().await.current_path = path.clone();
+        if let Some(path) = &path {
+            let title = metadata::lookup(path)
+                .map(|m| m.display_title().to_string())
+                .unwrap_or_else(|| {
+                    std::path::Path::new(path)
+                        .file_name()
+                        .and_then(|s| s.to_str())
+                        .unwrap_or("Wallpaper")
+                        .to_string()
+                });
+            Self::wallpaper_changed(&ctx, path, &title).await?;
+        }
+        Ok(path.unwrap_or_default())
+    }
+
     /// Get current configuration as JSON
     async fn get_config(&self) -> zbus::fdo::Result<String> {
         let state = self.state.read().await;
@@ -253,11 +434,47 @@ impl WallpaperService {
     }
 
     /// Set the Bing regional market
+    ///
+    /// # Returns
+    /// * Error: `InvalidArgs` if the code is malformed or neither a known nor
+    ///   a custom market
     async fn set_market(&self, market: String) -> zbus::fdo::Result<()> {
         let mut state = self.state.write().await;
+        // Start from the saved config so changes made in the settings window are kept
+        state.config = Config::load();
+        state.config.validate_market(&market)
+            .map_err(|e| zbus::fdo::Error::InvalidArgs(e.to_string()))?;
         state.config.market = market;
         state.config.save()
-            .map_err(zbus::fdo::Error::Failed)
+            .map_err(zbus::fdo::Error::from)
+    }
+
+    /// Add a market code that isn't in the built-in list (e.g., "es-CL")
+    ///
+    /// The code is only saved if the Bing API answers for it. It can then be
+    /// selected with `SetMarket` and appears in the settings dropdown.
+    ///
+    /// # Returns
+    /// * Error: `InvalidArgs` if the code is malformed, or the API error if Bing doesn't answer
+    async fn add_custom_market(&self, market: String) -> zbus::fdo::Result<()> {
+        let config = Config::load();
+        let (host, net) = (config.bing_host.clone(), NetworkSettings::from_config(&config));
+        // Probe the API (in a tokio runtime, since reqwest requires it)
+        let code = market.clone();
+        in_tokio_thread(move || async move { bing::probe_market(&net, &host, &code).await })
+            .await
+            .map_err(|e| match e {
+                Error::Config(m) => zbus::fdo::Error::InvalidArgs(m),
+                e => zbus::fdo::Error::from(e),
+            })?;
+
+        let mut state = self.state.write().await;
+        state.config = Config::load();
+        if !state.config.is_known_market(&market) {
+            state.config.custom_markets.push(market);
+        }
+        state.config.save()
+            .map_err(zbus::fdo::Error::from)
     }
 
     /// Get the wallpaper directory path
@@ -309,7 +526,9 @@ impl WallpaperService {
     /// Delete a wallpaper from history
     async fn delete_wallpaper(&self, path: String) -> zbus::fdo::Result<()> {
         std::fs::remove_file(&path)
-            .map_err(|e| zbus::fdo::Error::Failed(format!("Failed to delete: {e}")))
+            .map_err(|e| zbus::fdo::Error::Failed(format!("Failed to delete: {e}")))?;
+        metadata::forget(&path);
+        Ok(())
     }
 
     // === Signals ===
@@ -327,6 +546,55 @@ impl WallpaperService {
     async fn fetch_progress(ctx: &SignalContext<'_>, state: &str, message: &str) -> zbus::Result<()>;
 }
 
+/// Runs `work` while forwarding the status updates it sends to `status` as
+/// FetchProgress signals, and returns its result.
+async fn forward_progress<T>(
+    ctx: &SignalContext<'_>,
+    work: impl Future<Output = T>,
+    status: &mut tokio::sync::mpsc::UnboundedReceiver<(&'static str, String)>,
+) -> T {
+    tokio::pin!(work);
+    loop {
+        tokio::select! {
+            result = &mut work => break result,
+            Some((state, message)) = status.recv() => {
+                let _ = WallpaperService::fetch_progress(ctx, state, &message).await;
+            }
+        }
+    }
+}
+
+/// One attempt at fetching today's image info and downloading the image.
+///
+/// Sends `("downloading", message)` status updates for the FetchProgress signal.
+async fn fetch_and_download(
+    source: &dyn source::WallpaperSource,
+    wallpaper_dir: &str,
+    status: &tokio::sync::mpsc::UnboundedSender<(&'static str, String)>,
+) -> Result<(ImageMetadata, String), Error> {
+    let image = source.fetch_info().await?;
+    let _ = status.send(("downloading", format!("Downloading: {}", image.display_title())));
+
+    let progress = {
+        let (status, title) = (status.clone(), image.display_title().to_string());
+        move |done: u64, total: Option<u64>| {
+            let _ = status.send(("downloading", download_progress_message(&title, done, total)));
+        }
+    };
+    let path = source::download(source, &image, wallpaper_dir, &progress).await?;
+    Ok((image, path))
+}
+
+/// Formats byte progress for the FetchProgress signal,
+/// e.g. "Downloading: Title (1.2 of 4.8 MB)".
+fn download_progress_message(title: &str, done: u64, total: Option<u64>) -> String {
+    let mb = |bytes: u64| bytes as f64 / 1_000_000.0;
+    match total {
+        Some(total) => format!("Downloading: {} ({:.1} of {:.1} MB)", title, mb(done), mb(total)),
+        None => format!("Downloading: {} ({:.1} MB)", title, mb(done)),
+    }
+}
+
 /// Extracts the date from a wallpaper filename.
 ///
 /// Filenames follow the pattern "bing-{market}-YYYY-MM-DD.jpg".
@@ -360,53 +628,58 @@ pub fn extract_date_from_filename(filename: &str) -> String {
     name_without_ext.to_string()
 }
 
-/// Scans the wallpaper directory for downloaded images and returns them as WallpaperInfo.
+/// Lists the downloaded images and returns them as WallpaperInfo, newest first.
 ///
-/// This is the D-Bus version (returns WallpaperInfo for serialization over D-Bus).
-/// The settings window has its own version that returns HistoryItems (with PathBuf).
-fn scan_history(wallpaper_dir: &str) -> Vec<WallpaperInfo> {
-    let dir = std::path::Path::new(wallpaper_dir);
-    if !dir.exists() {
-        return Vec::new();
-    }
-
-    let mut items: Vec<WallpaperInfo> = std::fs::read_dir(dir)
-        .ok()
+/// Used by the D-Bus `GetHistory` method and, mapped to its own items, by the
+/// settings window. Images are found through the metadata index (see
+/// [`metadata::stored_images`]), and the same photo stored for several
+/// markets is listed only once.
+pub fn scan_history(wallpaper_dir: &str) -> Vec<WallpaperInfo> {
+    let mut items: Vec<(WallpaperInfo, Option<String>)> = metadata::stored_images(wallpaper_dir)
         .into_iter()
-        .flatten()
-        .filter_map(|entry| entry.ok())
-        .filter(|entry| {
-            entry.path().extension()
-                .map(|ext| ext == "jpg" || ext == "jpeg" || ext == "png")
-                .unwrap_or(false)
-        })
-        .map(|entry| {
-            let path = entry.path();
-            let path_str = path.to_string_lossy().to_string();
-            let filename = path.file_name()
-                .map(|s| s.to_string_lossy().to_string())
-                .unwrap_or_default();
-            let date = extract_date_from_filename(&filename);
-            WallpaperInfo { path: path_str, filename, date }
+        .map(|(path, metadata)| {
+            let key = metadata.as_ref().and_then(|m| m.dedup_key()).map(str::to_string);
+            (WallpaperInfo::with_metadata(path.to_string_lossy().to_string(), metadata.as_ref()), key)
         })
         .collect();
 
-    items.sort_by(|a, b| b.date.cmp(&a.date));
+    items.sort_by(|(a, _), (b, _)| b.date.cmp(&a.date).then_with(|| a.filename.cmp(&b.filename)));
+
+    // Hide copies of the same photo saved under other markets' filenames
+    let mut seen = std::collections::HashSet::new();
     items
+        .into_iter()
+        .filter(|(_, key)| key.as_ref().is_none_or(|key| seen.insert(key.clone())))
+        .map(|(item, _)| item)
+        .collect()
 }
 
+/// Filename prefixes of images saved by older versions, before the metadata index.
+const MANAGED_PREFIXES: &[&str] = &["bing-", "local-", "feed-"];
+
 /// Removes old wallpapers that are past the retention period.
 ///
-/// Scans the wallpaper directory for files matching "bing-*.jpg",
-/// parses the date from each filename, and deletes any older than `keep_days`.
+/// Images are found through the metadata index and expire by the date they
+/// were featured, whatever their filename or subdirectory. Images without an
+/// index entry are only touched if their filename shows this app saved them
+/// (`bing-*`, `local-*` or `feed-*` ending in a date), since other images may
+/// share the folder. Abandoned partial downloads expire by modification time,
+/// and date subdirectories left empty are removed. Processed copies made with
+/// outdated settings are dropped as well (see [`crate::process::prune_cache`]).
 ///
 /// # Arguments
-/// * `wallpaper_dir` - Path to the wallpaper storage directory
-/// * `keep_days` - Number of days to keep wallpapers (0 = keep forever)
+/// * `config` - The config whose `wallpaper_dir` is cleaned up, keeping
+///   images for `keep_days` (0 = keep forever) and the processed copies its
+///   settings still use
 ///
 /// # Returns
 /// The number of wallpapers deleted
-pub fn cleanup_old_wallpapers(wallpaper_dir: &str, keep_days: u32) -> usize {
+///
+/// Metadata index entries for deleted files are removed as well.
+pub fn cleanup_old_wallpapers(config: &Config) -> usize {
+    let (wallpaper_dir, keep_days) = (config.wallpaper_dir.as_str(), config.keep_days);
+    crate::process::prune_cache(wallpaper_dir, config, keep_days);
+
     if keep_days == 0 {
         return 0;
     }
@@ -419,32 +692,81 @@ pub fn cleanup_old_wallpapers(wallpaper_dir: &str, keep_days: u32) -> usize {
     let cutoff_date = chrono::Local::now().date_naive() - chrono::Duration::days(keep_days as i64);
     let mut deleted = 0;
 
-    if let Ok(entries) = std::fs::read_dir(dir) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            let filename = path.file_name()
-                .and_then(|s| s.to_str())
-                .unwrap_or("");
+    for (path, metadata) in metadata::stored_images(wallpaper_dir) {
+        let date = match &metadata {
+            Some(m) => chrono::NaiveDate::parse_from_str(&m.start_date, "%Y%m%d").ok(),
+            None => legacy_file_date(&path),
+        };
+        if date.is_some_and(|date| date < cutoff_date) && std::fs::remove_file(&path).is_ok() {
+            deleted += 1;
+            remove_empty_parents(&path, dir);
+        }
+    }
 
-            if !filename.starts_with("bing-") || !filename.ends_with(".jpg") {
-                continue;
-            }
+    remove_stale_partials(dir, keep_days, 2);
 
-            let name_without_ext = filename.strip_suffix(".jpg").unwrap_or(filename);
-            if name_without_ext.len() < 10 {
-                continue;
-            }
+    if deleted > 0 {
+        metadata::prune(wallpaper_dir);
+    }
 
-            let date_str = &name_without_ext[name_without_ext.len() - 10..];
-            if let Ok(file_date) = chrono::NaiveDate::parse_from_str(date_str, "%Y-%m-%d") {
-                if file_date < cutoff_date && std::fs::remove_file(&path).is_ok() {
-                    deleted += 1;
-                }
-            }
+    deleted
+}
+
+/// The date in the filename of an image saved by an older version
+/// (e.g., "bing-en-US-2026-02-05.jpg"), or None for any other file.
+fn legacy_file_date(path: &std::path::Path) -> Option<chrono::NaiveDate> {
+    let filename = path.file_name()?.to_str()?;
+    if !MANAGED_PREFIXES.iter().any(|prefix| filename.starts_with(prefix)) {
+        return None;
+    }
+    let name_without_ext = filename
+        .strip_suffix(".jpg")
+        .or_else(|| filename.strip_suffix(".jpeg"))
+        .or_else(|| filename.strip_suffix(".png"))?;
+    let date_str = name_without_ext.get(name_without_ext.len().checked_sub(10)?..)?;
+    chrono::NaiveDate::parse_from_str(date_str, "%Y-%m-%d").ok()
+}
+
+/// Removes the date subdirectories between `path` and `wallpaper_dir` that
+/// are empty now that `path` is gone.
+fn remove_empty_parents(path: &std::path::Path, wallpaper_dir: &std::path::Path) {
+    let mut parent = path.parent();
+    while let Some(dir) = parent {
+        // remove_dir only succeeds on empty directories
+        if dir == wallpaper_dir || std::fs::remove_dir(dir).is_err() {
+            break;
         }
+        parent = dir.parent();
     }
+}
 
-    deleted
+/// Removes partial downloads (`.{filename}.part`, and the `.part.url` file
+/// recording where each came from) not touched for `keep_days`, looking
+/// `depth` levels of subdirectories deep.
+fn remove_stale_partials(dir: &std::path::Path, keep_days: u32, depth: u32) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let max_age = std::time::Duration::from_secs(keep_days as u64 * 24 * 60 * 60);
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        if path.is_dir() {
+            if depth > 0 && !name.starts_with('.') {
+                remove_stale_partials(&path, keep_days, depth - 1);
+            }
+        } else if name.starts_with('.') && (name.ends_with(".part") || name.ends_with(".part.url")) {
+            let stale = entry.metadata()
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|modified| modified.elapsed().ok())
+                .is_some_and(|age| age > max_age);
+            if stale {
+                let _ = std::fs::remove_file(&path);
+            }
+        }
+    }
 }
 
 /// Runs a command on the host system, automatically handling Flatpak sandboxing.
@@ -485,52 +807,197 @@ fn spawn_host_command(cmd: &str) -> std::io::Result<std::process::Child> {
 
 /// Applies a wallpaper image to the COSMIC desktop.
 ///
-/// COSMIC desktop reads its background configuration from a RON (Rust Object Notation)
-/// file at `~/.config/cosmic/com.system76.CosmicBackground/v1/all`. This function:
-/// 1. Writes the config file with the new image path
-/// 2. Kills the `cosmic-bg` process (COSMIC's background renderer)
-/// 3. COSMIC automatically restarts `cosmic-bg`, which reads the new config
-/// 4. If COSMIC doesn't restart it, we start it manually
+/// COSMIC desktop reads its background configuration from RON (Rust Object Notation)
+/// files in `~/.config/cosmic/com.system76.CosmicBackground/v1/` (see
+/// [`crate::background`]). This function:
+/// 1. Sets the `all` entry's image to the new image path (or its
+///    [processed copy](crate::process), if post-processing, the caption or
+///    the dark variant is enabled), keeping the options set in COSMIC Settings
+/// 2. With [per-display wallpapers](crate::outputs), sets each display's
+///    entry and turns `same-on-all` off; otherwise turns it on
+/// 3. Writes all of this through cosmic-config, which `cosmic-bg` (COSMIC's
+///    background renderer) watches, so it switches to the new wallpaper without a restart
+/// 4. If cosmic-config can't be used (Flatpak) or fails, writes the files
+///    directly and [restarts cosmic-bg](restart_cosmic_bg) instead
+///
+/// Either way the settings are read back to check they took, and the ones
+/// from before are written back if they didn't or cosmic-bg doesn't come up
+/// (see [`switch_background`]).
+///
+/// The work (processing a copy can take a moment, and the restart waits for
+/// cosmic-bg) happens on its own thread, so awaiting this never blocks the
+/// async executor — zbus's or tokio's.
 ///
 /// # Arguments
-/// * `image_path` - Absolute path to the wallpaper image file
+/// * `image_path` - Absolute path to the wallpaper image file (the main one,
+///   for per-display wallpapers)
+pub async fn apply_cosmic_wallpaper(image_path: &str) -> Result<(), Error> {
+    let image_path = image_path.to_string();
+    on_own_thread(move || apply_blocking(&image_path, true)).await
+}
+
+/// Applies the wallpaper already shown again, e.g. to switch to its dark
+/// variant after a theme change, like [`apply_cosmic_wallpaper`] but keeping
+/// the undo point: reverting afterwards still goes back to the wallpaper
+/// from before the last real change, not to the other variant.
+pub async fn reapply_cosmic_wallpaper(image_path: &str) -> Result<(), Error> {
+    let image_path = image_path.to_string();
+    on_own_thread(move || apply_blocking(&image_path, false)).await
+}
+
+/// Goes back to the wallpaper shown before the last change (made by
+/// [`apply_cosmic_wallpaper`] or an earlier revert, so reverting twice
+/// returns to where it started).
+///
+/// # Returns
+/// * `Ok(Some(path))` - The image now shown (the original, not a processed copy)
+/// * `Ok(None)` - The previous background wasn't an image (e.g., a color)
+/// * `Err(Error)` - Nothing to go back to, its image is gone, or applying failed
+pub async fn revert_cosmic_wallpaper() -> Result<Option<String>, Error> {
+    on_own_thread(revert_blocking).await
+}
+
+/// Runs blocking work on a thread of its own and waits for it without
+/// blocking the async executor.
+async fn on_own_thread<T: Send + 'static>(
+    work: impl FnOnce() -> Result<T, Error> + Send + 'static,
+) -> Result<T, Error> {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    std::thread::spawn(move || {
+        let _ = tx.send(work());
+    });
+    rx.await.map_err(|_| Error::Io("Changing the wallpaper stopped unexpectedly".to_string()))?
+}
+
+/// Does the work of [`apply_cosmic_wallpaper`] (`remember`: whether the
+/// wallpaper it replaces becomes the undo point) on the calling thread.
+fn apply_blocking(image_path: &str, remember: bool) -> Result<(), Error> {
+    let config = Config::load();
+    let dir = background::config_dir().ok_or_else(|| Error::Io("Could not find home directory".to_string()))?;
+
+    // The original stays in history; the desktop gets the processed copy.
+    // filter_by_theme is left as the user set it: it only filters slideshow
+    // folders, so the dark variant is handled by re-applying on theme
+    // changes instead (see theme.rs)
+    let main = crate::process::prepare(image_path, &config);
+    let assignments: Vec<(String, String)> = crate::outputs::assign(&config, image_path)
+        .into_iter()
+        .map(|(output, path)| (output, crate::process::prepare(&path, &config)))
+        .collect();
+
+    switch_background(&dir, &Snapshot::for_images(&dir, &main, &assignments, &config), remember)
+}
+
+/// Does the work of [`revert_cosmic_wallpaper`] on the calling thread.
+fn revert_blocking() -> Result<Option<String>, Error> {
+    let dir = background::config_dir().ok_or_else(|| Error::Io("Could not find home directory".to_string()))?;
+    let previous = Snapshot::previous_path()
+        .and_then(|path| Snapshot::previous(&path))
+        .ok_or_else(|| Error::Config("No previous wallpaper to go back to".to_string()))?;
+    if let Some(missing) = previous.missing_image() {
+        return Err(Error::Io(format!("The previous wallpaper no longer exists: {}", missing.display())));
+    }
+
+    switch_background(&dir, &previous, true)?;
+    let config = Config::load();
+    Ok(previous
+        .image_path()
+        .map(|path| path.to_string_lossy().to_string())
+        .map(|path| crate::process::original_path(&config, &path).unwrap_or(path)))
+}
+
+/// Switches COSMIC's background settings in `dir` to `target`, checking
+/// that they took and rolling back if not.
+///
+/// 1. Takes a [`Snapshot`] of the current settings of the keys `target` writes
+/// 2. Writes `target` through cosmic-config, reads it back, and checks that
+///    cosmic-bg is still running
+/// 3. Failing that, writes the files directly, reads them back and restarts cosmic-bg
+/// 4. If that fails too, writes the snapshot back (and restarts cosmic-bg
+///    with it), so the desktop keeps its old wallpaper rather than none
+/// 5. On success, remembers the snapshot for [`revert_cosmic_wallpaper`]
+///    if `remember` is set (see [`keep_undo_point`])
+fn switch_background(dir: &std::path::Path, target: &Snapshot, remember: bool) -> Result<(), Error> {
+    let previous = Snapshot::current(dir, target);
+
+    let mut switched = false;
+    if let Some(backend) = background::Backend::cosmic_config() {
+        match target.write(&backend).and_then(|()| target.verify(dir)) {
+            Ok(()) => {
+                // A bad image can make cosmic-bg exit while loading it
+                std::thread::sleep(SETTLE_TIME);
+                switched = cosmic_bg_running() != Some(false);
+                if !switched {
+                    eprintln!("cosmic-bg isn't running, starting it");
+                }
+            }
+            Err(e) => eprintln!("{e}, restarting cosmic-bg instead"),
+        }
+    }
+    if !switched {
+        let files = background::Backend::Files(dir.to_path_buf());
+        let result = target.write(&files)
+            .and_then(|()| target.verify(dir))
+            .and_then(|()| restart_cosmic_bg());
+        if let Err(e) = result {
+            if let Err(restore_error) = previous.write(&files) {
+                eprintln!("Failed to restore the previous wallpaper: {restore_error}");
+            } else if let Err(restart_error) = restart_cosmic_bg() {
+                eprintln!("cosmic-bg didn't come back up with the previous wallpaper either: {restart_error}");
+            }
+            return Err(e.context("Kept the previous wallpaper"));
+        }
+    }
+
+    match Snapshot::previous_path() {
+        Some(undo_file) => keep_undo_point(&undo_file, &previous, target, remember),
+        None => eprintln!("Could not determine config path, the change can't be undone"),
+    }
+    Ok(())
+}
+
+/// Makes `previous`, the settings a switch to `target` replaced, the undo
+/// point in `undo_file`.
 ///
-/// # Why we kill cosmic-bg
-/// COSMIC doesn't have a "reload config" API — the only way to make it
-/// pick up a new wallpaper is to restart the background process.
-pub fn apply_cosmic_wallpaper(image_path: &str) -> Result<(), String> {
-    // We use home_dir() instead of config_dir() because in Flatpak,
-    // config_dir() returns the sandboxed path (~/.var/app/APP_ID/config/),
-    // but COSMIC reads from the real ~/.config/ on the host.
-    let config_path = dirs::home_dir()
-        .ok_or("Could not find home directory")?
-        .join(".config/cosmic/com.system76.CosmicBackground/v1/all");
-
-    // Write the COSMIC background config in RON format.
-    // RON is Rust's native serialization format, similar to JSON but Rust-flavored.
-    let config_content = format!(
-        r#"(
-    output: "all",
-    source: Path("{}"),
-    filter_by_theme: false,
-    rotation_frequency: 300,
-    filter_method: Lanczos,
-    scaling_mode: Zoom,
-    sampling_method: Alphanumeric,
-)"#,
-        image_path
-    );
-
-    // Ensure the config directory exists
-    if let Some(parent) = config_path.parent() {
-        std::fs::create_dir_all(parent)
-            .map_err(|e| format!("Failed to create config dir: {e}"))?;
-    }
-
-    // Write the config file
-    std::fs::write(&config_path, config_content)
-        .map_err(|e| format!("Failed to write config: {e}"))?;
+/// A re-apply (`remember` off) or a switch to what was already shown keeps
+/// the undo point it had, so reverting goes back to the wallpaper before it
+/// rather than to another variant of the same one. Losing the undo point
+/// isn't worth failing a wallpaper change that worked, so errors are only logged.
+fn keep_undo_point(undo_file: &std::path::Path, previous: &Snapshot, target: &Snapshot, remember: bool) {
+    if remember && previous != target {
+        if let Err(e) = previous.remember(undo_file) {
+            eprintln!("{e}");
+        }
+    }
+}
 
+/// How long cosmic-bg gets to load a new wallpaper before checking it is still running.
+const SETTLE_TIME: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Whether cosmic-bg is running, or None if `pgrep` couldn't tell.
+fn cosmic_bg_running() -> Option<bool> {
+    let output = run_host_command("pgrep", &["-x", "cosmic-bg"]).ok()?;
+    // pgrep exits with 1 when nothing matches, and 2 or more on errors
+    match output.status.code() {
+        Some(0) => Some(true),
+        Some(1) => Some(false),
+        _ => None,
+    }
+}
+
+/// Makes cosmic-bg load its config again by restarting it.
+///
+/// 1. Kills the `cosmic-bg` process
+/// 2. COSMIC automatically restarts `cosmic-bg`, which reads the new config
+/// 3. If COSMIC doesn't restart it, we start it manually
+///
+/// This blocks for a second or more and the desktop flashes while cosmic-bg
+/// is gone, which is why it is only the fallback.
+///
+/// # Returns
+/// * `Err(Error)` - cosmic-bg couldn't be started, or exited right away
+///   (e.g., because it can't load the new wallpaper)
+fn restart_cosmic_bg() -> Result<(), Error> {
     // Send SIGTERM to cosmic-bg to trigger a restart with the new config
     let _ = run_host_command("pkill", &["-TERM", "-x", "cosmic-bg"]);
 
@@ -538,15 +1005,57 @@ pub fn apply_cosmic_wallpaper(image_path: &str) -> Result<(), String> {
     std::thread::sleep(std::time::Duration::from_millis(1000));
 
     // Verify cosmic-bg is running; if COSMIC didn't restart it, start it manually
-    let check = run_host_command("pgrep", &["-x", "cosmic-bg"]);
-    match check {
-        Ok(output) if output.status.success() => Ok(()),  // Already running
-        _ => {
-            // Not running — start it ourselves
-            spawn_host_command("cosmic-bg")
-                .map_err(|e| format!("Failed to start cosmic-bg: {e}"))?;
-            std::thread::sleep(std::time::Duration::from_millis(500));
-            Ok(())
-        }
+    if cosmic_bg_running() == Some(true) {
+        return Ok(());
+    }
+    spawn_host_command("cosmic-bg")
+        .map_err(|e| Error::Io(format!("Failed to start cosmic-bg: {e}")))?;
+    std::thread::sleep(std::time::Duration::from_millis(500));
+
+    // Unknown counts as running: without pgrep there's no telling
+    match cosmic_bg_running() {
+        Some(false) => Err(Error::Io("cosmic-bg exited right after starting".to_string())),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_reapply_keeps_the_undo_point() {
+        let dir = std::env::temp_dir().join(format!("bing-wallpaper-undo-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let cosmic = dir.join("cosmic");
+        let undo_file = dir.join("previous_wallpaper.json");
+        let files = background::Backend::Files(cosmic.clone());
+        let config = Config::default();
+
+        // What switch_background does, minus the checks and cosmic-bg
+        let switch = |target: &Snapshot, remember: bool| {
+            let previous = Snapshot::current(&cosmic, target);
+            target.write(&files).unwrap();
+            keep_undo_point(&undo_file, &previous, target, remember);
+        };
+        let showing = |image: &str| Snapshot::for_images(&cosmic, image, &[], &config);
+
+        switch(&showing("/pictures/a.jpg"), true);
+        switch(&showing("/pictures/b.jpg"), true);
+        // The theme turned dark: B's dark copy is re-applied
+        switch(&showing("/pictures/.processed/b-0123456789ab.jpg"), false);
+
+        // Reverting goes back to A, not to the light B
+        let previous = Snapshot::previous(&undo_file).unwrap();
+        assert_eq!(previous.image_path(), Some(PathBuf::from("/pictures/a.jpg")));
+        switch(&previous, true);
+        assert_eq!(Snapshot::current(&cosmic, &previous).image_path(), Some(PathBuf::from("/pictures/a.jpg")));
+
+        // And reverting again returns to what was shown
+        let previous = Snapshot::previous(&undo_file).unwrap();
+        assert_eq!(previous.image_path(), Some(PathBuf::from("/pictures/.processed/b-0123456789ab.jpg")));
+
+        let _ = std::fs::remove_dir_all(&dir);
     }
 }
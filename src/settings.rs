@@ -8,9 +8,14 @@
 //!
 //! ## Features
 //! - Preview today's Bing wallpaper
-//! - Browse and apply previously downloaded wallpapers
+//! - Browse and apply previously downloaded wallpapers (with titles from the metadata index)
+//! - Choose the wallpaper source (Bing or a local folder)
 //! - Select regional Bing market
+//! - Choose the downloaded image resolution
 //! - Enable/disable daily auto-update timer
+//! - Draw the caption (title, date, copyright) onto the wallpaper
+//! - Use a darker wallpaper while the COSMIC theme is dark
+//! - Show a different day or market on each display
 //! - Delete old wallpapers
 
 // --- COSMIC framework imports ---
@@ -18,16 +23,21 @@ use cosmic::app::Core;                  // Core app state provided by the framew
 use cosmic::iced::{Length, ContentFit}; // Length = sizing, ContentFit = image scaling
 use cosmic::widget::{                   // UI building blocks
     self, button, column, container, row, text,
-    dropdown, scrollable, settings, toggler,
+    dropdown, scrollable, settings, text_input, toggler,
 };
 use cosmic::{Action, Application, Element, Task}; // Core traits and types
 use std::path::PathBuf;                 // Filesystem path type
 
 // --- Internal module imports ---
-use crate::bing::{BingImage, fetch_bing_image_info, download_image}; // Bing API client
-use crate::config::{Config, MARKETS};   // User config and market list
+use crate::config::{Config, FilterMethod, MarketMode, OutputWallpaper, OverlayPosition, Resolution, ScalingMode, SourceKind, Subdirectories}; // User config, sources, image sizes and market modes
 use crate::dbus_client::WallpaperClient; // D-Bus client to talk to the panel applet
-use crate::service::{cleanup_old_wallpapers, extract_date_from_filename}; // Shared utilities
+use crate::error::Error;                 // Typed fetch/apply errors
+use crate::http::NetworkSettings;        // Proxy and certificates for requests
+use crate::metadata::{self, ImageMetadata}; // Per-image metadata index
+use crate::network::NetworkStatus;       // Network state for the metered policy
+use crate::retry;                        // Shared retry/backoff policy
+use crate::source;                       // Wallpaper sources (Bing, local folder)
+use crate::service::{cleanup_old_wallpapers, WallpaperInfo}; // Shared utilities and history entries
 
 /// Unique application identifier for the settings window.
 /// Uses a different ID from the applet so COSMIC treats them as separate apps.
@@ -49,8 +59,8 @@ pub struct SettingsApp {
     core: Core,
     /// User configuration loaded from ~/.config/cosmic-bing-wallpaper/config.json
     config: Config,
-    /// Metadata for the currently fetched Bing image (title, copyright, URL)
-    current_image: Option<BingImage>,
+    /// Metadata for the image being previewed (title, copyright, source link)
+    current_image: Option<ImageMetadata>,
     /// Local filesystem path to the downloaded image file (for preview display)
     image_path: Option<String>,
     /// Status message displayed at the bottom of the window (e.g., "Ready", "Error: ...")
@@ -59,12 +69,57 @@ pub struct SettingsApp {
     is_loading: bool,
     /// List of previously downloaded wallpaper files found in the wallpaper directory
     history: Vec<HistoryItem>,
-    /// Index of the currently selected market in the dropdown (maps to MARKETS array)
+    /// Index of the currently selected market in the dropdown (maps to market_codes)
     selected_market_idx: usize,
     /// Which view/screen is currently displayed (Main or History)
     view_mode: ViewMode,
     /// Pre-computed list of market display names for the dropdown widget
+    /// (the known markets followed by the user's custom ones)
     market_names: Vec<String>,
+    /// Market codes in the same order as market_names
+    market_codes: Vec<String>,
+    /// Text of the "add custom market" input (e.g., "es-CL")
+    custom_market: String,
+    /// Caption language dropdown: "Same as region" followed by market_names
+    caption_names: Vec<String>,
+    /// Index of the selected caption language (0 = same as region, else market_codes[idx - 1])
+    selected_caption_idx: usize,
+    /// Index of the selected market mode in the dropdown (maps to MarketMode::ALL)
+    selected_market_mode_idx: usize,
+    /// Pre-computed list of market mode labels for the dropdown widget
+    market_mode_names: Vec<String>,
+    /// Text of the market list input (e.g., "en-US, ja-JP, de-DE")
+    market_list: String,
+    /// Index of the selected source in the dropdown (maps to SourceKind::ALL)
+    selected_source_idx: usize,
+    /// Pre-computed list of source labels for the dropdown widget
+    source_names: Vec<String>,
+    /// Index of the selected resolution (maps to Resolution::PRESETS, last entry = Custom)
+    selected_resolution_idx: usize,
+    /// Pre-computed list of resolution labels for the dropdown widget
+    resolution_names: Vec<String>,
+    /// Text of the custom resolution input (e.g., "1366x768")
+    custom_resolution: String,
+    /// Index of the selected subfolder layout (maps to Subdirectories::ALL)
+    selected_subdirectories_idx: usize,
+    /// Pre-computed list of subfolder layout labels for the dropdown widget
+    subdirectories_names: Vec<String>,
+    /// Index of the selected caption corner (maps to OverlayPosition::ALL)
+    selected_overlay_position_idx: usize,
+    /// Pre-computed list of caption corner labels for the dropdown widget
+    overlay_position_names: Vec<String>,
+    /// Index of the selected scaling mode (maps to ScalingMode::ALL)
+    selected_scaling_idx: usize,
+    /// Pre-computed list of scaling mode labels for the dropdown widget
+    scaling_names: Vec<String>,
+    /// Index of the selected filter method (maps to FilterMethod::ALL)
+    selected_filter_method_idx: usize,
+    /// Pre-computed list of filter method labels for the dropdown widget
+    filter_method_names: Vec<String>,
+    /// Displays found at startup, for per-display wallpapers
+    output_names: Vec<String>,
+    /// Per-display day choices ("Same Day", "Day Before", ...), indexed by days_ago
+    days_ago_names: Vec<String>,
     /// Current status of the auto-update timer (checked via D-Bus every 5 seconds)
     timer_status: TimerStatus,
     /// Path of a wallpaper the user wants to delete (shown with confirm/cancel buttons)
@@ -78,8 +133,27 @@ pub struct HistoryItem {
     pub path: PathBuf,
     /// Just the filename (e.g., "bing-en-US-2026-02-05.jpg")
     pub filename: String,
-    /// Date extracted from the filename (e.g., "2026-02-05")
+    /// Date the image was featured (e.g., "2026-02-05")
     pub date: String,
+    /// Image title from the metadata index (empty if unknown)
+    pub title: String,
+    /// Copyright/attribution text from the metadata index (empty if unknown)
+    pub copyright: String,
+    /// Link to more information about the image (empty if unknown)
+    pub copyright_link: String,
+}
+
+impl From<WallpaperInfo> for HistoryItem {
+    fn from(info: WallpaperInfo) -> Self {
+        Self {
+            path: PathBuf::from(info.path),
+            filename: info.filename,
+            date: info.date,
+            title: info.title,
+            copyright: info.copyright,
+            copyright_link: info.copyright_link,
+        }
+    }
 }
 
 /// Which screen/view is currently displayed in the settings window.
@@ -121,20 +195,86 @@ pub enum Message {
     // === Image Fetching (multi-step async chain) ===
     /// User clicked "Fetch Today's Wallpaper" — starts the fetch pipeline
     FetchToday,
-    /// Step 1 result: Got image metadata from Bing API (or error)
-    FetchedImageInfo(Result<BingImage, String>),
+    /// Step 1 result: Got image metadata from the wallpaper source (or error)
+    FetchedImageInfo(Result<ImageMetadata, Error>),
     /// Step 2 result: Image downloaded to disk (path) or error
-    DownloadedImage(Result<String, String>),
+    DownloadedImage(Result<String, Error>),
 
     // === Wallpaper Application ===
     /// User clicked "Apply" on a history item — apply that wallpaper
     ApplyHistoryWallpaper(PathBuf),
     /// Apply operation completed (success or error)
-    AppliedWallpaper(Result<(), String>),
+    AppliedWallpaper(Result<(), Error>),
 
     // === UI Navigation ===
-    /// User selected a different market from the dropdown (index into MARKETS array)
+    /// User selected a wallpaper source from the dropdown (index into SourceKind::ALL)
+    SourceSelected(usize),
+    /// User edited the local folder path
+    LocalFolderChanged(String),
+    /// User edited the custom feed URL
+    FeedUrlChanged(String),
+    /// User edited the JSON image URL selector for the custom feed
+    FeedImageSelectorChanged(String),
+    /// User edited the JSON title selector for the custom feed
+    FeedTitleSelectorChanged(String),
+    /// User selected a different market from the dropdown (index into market_codes)
     MarketSelected(usize),
+    /// User edited the custom market code input
+    CustomMarketChanged(String),
+    /// User clicked "Add" next to the custom market code
+    AddCustomMarket,
+    /// The Bing API was probed for the custom market (Ok = the code, accepted)
+    CustomMarketProbed(Result<String, Error>),
+    /// User selected a caption language (0 = same as region, else market_codes[idx - 1])
+    CaptionMarketSelected(usize),
+    /// User selected a market mode from the dropdown (index into MarketMode::ALL)
+    MarketModeSelected(usize),
+    /// User edited the list of markets to rotate through / pick from
+    MarketListChanged(String),
+    /// User selected a resolution from the dropdown (index into Resolution::PRESETS, or Custom)
+    ResolutionSelected(usize),
+    /// User edited the custom resolution text input
+    CustomResolutionChanged(String),
+    /// User edited the filename template (e.g., "{date} {title}")
+    FilenameTemplateChanged(String),
+    /// User selected a subfolder layout (index into Subdirectories::ALL)
+    SubdirectoriesSelected(usize),
+    /// User toggled post-processing on or off
+    ProcessingToggled(bool),
+    /// User edited the crop aspect ratio (e.g., "21:9", "display")
+    CropAspectChanged(String),
+    /// User moved the blur slider (pixels)
+    BlurChanged(u32),
+    /// User moved the brightness slider (-100 to 100 percent)
+    BrightnessChanged(i32),
+    /// User moved the desaturate slider (percent)
+    DesaturateChanged(u32),
+    /// User moved the vignette slider (percent)
+    VignetteChanged(u32),
+    /// User toggled the caption overlay on or off
+    OverlayToggled(bool),
+    /// User selected the caption corner (index into OverlayPosition::ALL)
+    OverlayPositionSelected(usize),
+    /// User moved the caption font size slider (pixels at 1080p)
+    FontSizeChanged(u32),
+    /// User moved the caption opacity slider (percent)
+    OverlayOpacityChanged(u32),
+    /// User moved the caption background slider (percent)
+    ScrimChanged(u32),
+    /// User selected how the wallpaper fills the display (index into ScalingMode::ALL)
+    ScalingSelected(usize),
+    /// User selected the resampling filter (index into FilterMethod::ALL)
+    FilterMethodSelected(usize),
+    /// User toggled different wallpapers per display
+    PerOutputToggled(bool),
+    /// User picked a display's day (index into config.outputs, days before the main wallpaper)
+    OutputDaySelected(usize, usize),
+    /// User picked a display's market (index into config.outputs; 0 = same as region, else market_codes[idx - 1])
+    OutputMarketSelected(usize, usize),
+    /// User toggled the darker wallpaper for dark mode
+    DarkVariantToggled(bool),
+    /// User moved the dark mode dimming slider (percent)
+    DarkDimChanged(u32),
     /// User clicked "History" button — switch to history view
     ShowHistory,
     /// User clicked back button — switch to main view
@@ -147,6 +287,8 @@ pub enum Message {
     ConfirmDeleteHistoryItem,
     /// User cancelled deletion — hide confirmation buttons
     CancelDeleteHistoryItem,
+    /// User clicked "Learn More" — open the image's source link in the browser
+    OpenLink(String),
 
     // === Timer Management (via D-Bus to applet) ===
     /// Periodic check: query the applet for current timer state
@@ -199,19 +341,86 @@ impl Application for SettingsApp {
     /// 2. Sync current wallpaper path from the applet
     /// 3. Optionally auto-fetch today's wallpaper (if timer is enabled and fetch_on_startup is true)
     fn init(core: Core, _flags: Self::Flags) -> (Self, Task<Action<Self::Message>>) {
-        let config = Config::load();
+        let mut config = Config::load();
 
-        // Find which market in the dropdown matches the user's saved config.
+        // Market dropdown: the known markets followed by the user's custom ones.
+        // Find which one matches the user's saved config;
         // position() returns the index, or 0 (first item) as fallback.
-        let selected_market_idx = MARKETS
+        let (market_codes, market_names): (Vec<String>, Vec<String>) =
+            config.market_choices().into_iter().unzip();
+        let selected_market_idx = market_codes
             .iter()
-            .position(|m| m.code == config.market)
+            .position(|code| *code == config.market)
             .unwrap_or(0);
+        let (caption_names, selected_caption_idx) =
+            caption_choices(&market_codes, &market_names, &config.caption_market);
 
         // Scan the wallpaper directory for existing downloaded images
         let history = scan_history(&config.wallpaper_dir);
-        // Pre-compute display names for the market dropdown widget
-        let market_names: Vec<String> = MARKETS.iter().map(|m| m.name.to_string()).collect();
+
+        let market_mode_names: Vec<String> = MarketMode::ALL.iter().map(|m| m.label().to_string()).collect();
+        let selected_market_mode_idx = MarketMode::ALL
+            .iter()
+            .position(|m| *m == config.market_mode)
+            .unwrap_or(0);
+        let market_list = config.markets.join(", ");
+
+        let source_names: Vec<String> = SourceKind::ALL.iter().map(|s| s.label().to_string()).collect();
+        let selected_source_idx = SourceKind::ALL
+            .iter()
+            .position(|s| *s == config.source)
+            .unwrap_or(0);
+
+        // Resolution dropdown: the presets followed by a "Custom" entry
+        let mut resolution_names: Vec<String> = Resolution::PRESETS.iter().map(|r| r.label()).collect();
+        resolution_names.push("Custom".to_string());
+        let (selected_resolution_idx, custom_resolution) = match &config.resolution {
+            Resolution::Custom(size) => (Resolution::PRESETS.len(), size.clone()),
+            preset => (
+                Resolution::PRESETS.iter().position(|r| r == preset).unwrap_or(0),
+                String::new(),
+            ),
+        };
+
+        let subdirectories_names: Vec<String> = Subdirectories::ALL.iter().map(|s| s.label().to_string()).collect();
+        let selected_subdirectories_idx = Subdirectories::ALL
+            .iter()
+            .position(|s| *s == config.subdirectories)
+            .unwrap_or(0);
+
+        let overlay_position_names: Vec<String> = OverlayPosition::ALL.iter().map(|p| p.label().to_string()).collect();
+        let selected_overlay_position_idx = OverlayPosition::ALL
+            .iter()
+            .position(|p| *p == config.overlay.position)
+            .unwrap_or(0);
+
+        let scaling_names: Vec<String> = ScalingMode::ALL.iter().map(|m| m.label().to_string()).collect();
+        let selected_scaling_idx = ScalingMode::ALL
+            .iter()
+            .position(|m| *m == config.scaling_mode)
+            .unwrap_or(0);
+        let filter_method_names: Vec<String> = FilterMethod::ALL.iter().map(|m| m.label().to_string()).collect();
+        let selected_filter_method_idx = FilterMethod::ALL
+            .iter()
+            .position(|m| *m == config.filter_method)
+            .unwrap_or(0);
+
+        // Displays plugged in since per-display wallpapers were set up show the main one
+        let output_names = crate::outputs::available_outputs();
+        if !config.outputs.is_empty() {
+            for name in &output_names {
+                if !config.outputs.iter().any(|o| &o.output == name) {
+                    config.outputs.push(OutputWallpaper { output: name.clone(), ..OutputWallpaper::default() });
+                }
+            }
+        }
+        let days_ago_names: Vec<String> = (0..crate::bing::ARCHIVE_DAYS)
+            .map(|days| match days {
+                0 => "Same Day".to_string(),
+                1 => "Day Before".to_string(),
+                n => format!("{n} Days Before"),
+            })
+            .collect();
 
         let app = Self {
             core,
@@ -224,6 +433,28 @@ impl Application for SettingsApp {
             selected_market_idx,
             view_mode: ViewMode::Main,
             market_names,
+            market_codes,
+            custom_market: String::new(),
+            caption_names,
+            selected_caption_idx,
+            selected_market_mode_idx,
+            market_mode_names,
+            market_list,
+            selected_source_idx,
+            source_names,
+            selected_resolution_idx,
+            resolution_names,
+            custom_resolution,
+            selected_subdirectories_idx,
+            subdirectories_names,
+            selected_overlay_position_idx,
+            overlay_position_names,
+            selected_scaling_idx,
+            scaling_names,
+            selected_filter_method_idx,
+            filter_method_names,
+            output_names,
+            days_ago_names,
             timer_status: TimerStatus::Checking,
             pending_delete: None,
         };
@@ -276,35 +507,46 @@ impl Application for SettingsApp {
             Message::FetchToday => {
                 self.status_message = "Fetching image info...".to_string();
                 self.is_loading = true;
-                let market = self.config.market.clone();
+                let config = self.config.clone();
 
-                // Start async operation: call the Bing API
+                // Start async operation: ask the configured source (Bing API or local folder)
                 // When done, the result becomes a FetchedImageInfo message
                 Task::perform(
-                    async move { fetch_bing_image_info(&market).await },
+                    async move {
+                        // Only the info is fetched here, so the network state doesn't matter
+                        let source = source::from_config(&config, NetworkStatus::default());
+                        retry::retry(&config.retry, || source.fetch_info(), |r| eprintln!("{}", r.message())).await
+                    },
                     |result| Action::App(Message::FetchedImageInfo(result)),
                 )
             }
 
             // --- Fetch pipeline: step 2 of 4 ---
-            // Got image metadata from Bing API
+            // Got image metadata from the source
             Message::FetchedImageInfo(result) => {
                 match result {
                     Ok(image) => {
                         // Save image info for display (title, copyright)
                         self.current_image = Some(image.clone());
                         self.status_message = "Downloading image...".to_string();
-                        let dir = self.config.wallpaper_dir.clone();
-                        let market = self.config.market.clone();
+                        let config = self.config.clone();
 
                         // Start async operation: download the actual image file
                         Task::perform(
-                            async move { download_image(&image, &dir, &market).await },
+                            async move {
+                                let network = crate::network::status_for(config.metered).await;
+                                let source = source::from_config(&config, network);
+                                retry::retry(
+                                    &config.retry,
+                                    || source::download(source.as_ref(), &image, &config.wallpaper_dir, &crate::http::no_progress),
+                                    |r| eprintln!("{}", r.message()),
+                                ).await
+                            },
                             |result| Action::App(Message::DownloadedImage(result)),
                         )
                     }
                     Err(e) => {
-                        self.status_message = format!("Error: {e}");
+                        self.status_message = e.status_message();
                         self.is_loading = false;
                         Task::none()
                     }
@@ -318,9 +560,12 @@ impl Application for SettingsApp {
                     Ok(path) => {
                         // Store the path so the preview widget can display the image
                         self.image_path = Some(path.clone());
+                        if let Some(info) = metadata::lookup(&path) {
+                            self.current_image = Some(info);
+                        }
 
                         // Clean up old wallpapers beyond the keep_days limit
-                        let deleted = cleanup_old_wallpapers(&self.config.wallpaper_dir, self.config.keep_days);
+                        let deleted = cleanup_old_wallpapers(&self.config);
                         if deleted > 0 {
                             self.status_message = format!(
                                 "Downloaded ({deleted} old cleaned up). Applying...",
@@ -340,7 +585,7 @@ impl Application for SettingsApp {
                     }
                     Err(e) => {
                         self.is_loading = false;
-                        self.status_message = format!("Error: {e}");
+                        self.status_message = e.status_message();
                         Task::none()
                     }
                 }
@@ -348,7 +593,11 @@ impl Application for SettingsApp {
 
             // User clicked "Apply" on a history item
             Message::ApplyHistoryWallpaper(path) => {
-                self.apply_wallpaper_from_path(path.to_string_lossy().to_string())
+                let path = path.to_string_lossy().to_string();
+                // Show the applied image and its details in the main view
+                self.current_image = metadata::lookup(&path);
+                self.image_path = Some(path.clone());
+                self.apply_wallpaper_from_path(path)
             }
 
             // --- Fetch pipeline: step 4 of 4 ---
@@ -360,7 +609,7 @@ impl Application for SettingsApp {
                         self.status_message = "Wallpaper applied!".to_string();
                     }
                     Err(e) => {
-                        self.status_message = format!("Error: {e}");
+                        self.status_message = e.status_message();
                     }
                 }
                 Task::none()
@@ -368,15 +617,328 @@ impl Application for SettingsApp {
 
             // User selected a different market from the dropdown
             Message::MarketSelected(idx) => {
-                if idx < MARKETS.len() {
+                if let Some(code) = self.market_codes.get(idx) {
                     self.selected_market_idx = idx;
                     // Update config and save to disk immediately
-                    self.config.market = MARKETS[idx].code.to_string();
+                    self.config.market = code.clone();
+                    let _ = self.config.save();
+                }
+                Task::none()
+            }
+
+            Message::CustomMarketChanged(code) => {
+                self.custom_market = code;
+                Task::none()
+            }
+
+            // Only accept a custom market once Bing has answered for it
+            Message::AddCustomMarket => {
+                let code = self.custom_market.trim().to_string();
+                let host = self.config.bing_host.clone();
+                let net = NetworkSettings::from_config(&self.config);
+                self.status_message = format!("Checking market {code}...");
+                Task::perform(
+                    async move { crate::bing::probe_market(&net, &host, &code).await.map(|_| code) },
+                    |result| Action::App(Message::CustomMarketProbed(result)),
+                )
+            }
+
+            Message::CustomMarketProbed(result) => {
+                match result {
+                    Ok(code) => {
+                        if !self.config.is_known_market(&code) {
+                            self.config.custom_markets.push(code.clone());
+                        }
+                        // Select the new market right away
+                        self.config.market = code.clone();
+                        let _ = self.config.save();
+
+                        let (codes, names) = self.config.market_choices().into_iter().unzip();
+                        self.market_codes = codes;
+                        self.market_names = names;
+                        self.selected_market_idx = self.market_codes
+                            .iter()
+                            .position(|c| *c == code)
+                            .unwrap_or(0);
+                        (self.caption_names, self.selected_caption_idx) =
+                            caption_choices(&self.market_codes, &self.market_names, &self.config.caption_market);
+                        self.custom_market.clear();
+                        self.status_message = format!("Added market {code}");
+                    }
+                    Err(e) => {
+                        self.status_message = e.status_message();
+                    }
+                }
+                Task::none()
+            }
+
+            // Takes effect from the next fetch; images already downloaded keep their captions
+            Message::CaptionMarketSelected(idx) => {
+                let code = match idx {
+                    0 => Some(String::new()),
+                    _ => self.market_codes.get(idx - 1).cloned(),
+                };
+                if let Some(code) = code {
+                    match self.config.validate_optional_market(&code) {
+                        Ok(()) => {
+                            self.selected_caption_idx = idx;
+                            self.config.caption_market = code;
+                            let _ = self.config.save();
+                        }
+                        Err(e) => self.status_message = e.status_message(),
+                    }
+                }
+                Task::none()
+            }
+
+            // User selected how the market is chosen each day
+            Message::MarketModeSelected(idx) => {
+                if let Some(mode) = MarketMode::ALL.get(idx) {
+                    self.selected_market_mode_idx = idx;
+                    self.config.market_mode = *mode;
+                    let _ = self.config.save();
+                }
+                Task::none()
+            }
+
+            // User edited the market list; keep the raw text so typing isn't
+            // disturbed, and save the comma-separated codes in order. Codes that
+            // don't validate (including one still being typed) are left out
+            Message::MarketListChanged(list) => {
+                let mut markets = Vec::new();
+                let mut invalid = None;
+                for code in list.split(',').map(str::trim).filter(|code| !code.is_empty()) {
+                    match self.config.validate_market(code) {
+                        Ok(()) => markets.push(code.to_string()),
+                        Err(e) => invalid = Some(e),
+                    }
+                }
+                if let Some(e) = invalid {
+                    self.status_message = e.status_message();
+                }
+                self.config.markets = markets;
+                self.market_list = list;
+                let _ = self.config.save();
+                Task::none()
+            }
+
+            // User selected a wallpaper source from the dropdown
+            Message::SourceSelected(idx) => {
+                if let Some(kind) = SourceKind::ALL.get(idx) {
+                    self.selected_source_idx = idx;
+                    self.config.source = *kind;
+                    let _ = self.config.save();
+                }
+                Task::none()
+            }
+
+            // User typed a local folder path; saved as-is (checked when fetching)
+            Message::LocalFolderChanged(folder) => {
+                self.config.local_folder = folder;
+                let _ = self.config.save();
+                Task::none()
+            }
+
+            // Custom feed settings; saved as-is (checked when fetching)
+            Message::FeedUrlChanged(url) => {
+                self.config.feed_url = url;
+                let _ = self.config.save();
+                Task::none()
+            }
+
+            Message::FeedImageSelectorChanged(selector) => {
+                self.config.feed_image_selector = selector;
+                let _ = self.config.save();
+                Task::none()
+            }
+
+            Message::FeedTitleSelectorChanged(selector) => {
+                self.config.feed_title_selector = selector;
+                let _ = self.config.save();
+                Task::none()
+            }
+
+            // User selected a resolution from the dropdown
+            Message::ResolutionSelected(idx) => {
+                self.selected_resolution_idx = idx;
+                if let Some(preset) = Resolution::PRESETS.get(idx) {
+                    self.config.resolution = preset.clone();
+                    let _ = self.config.save();
+                } else if crate::display::parse_mode(&self.custom_resolution).is_some() {
+                    // "Custom" selected with a valid size already typed in
+                    self.config.resolution = Resolution::Custom(self.custom_resolution.trim().to_string());
+                    let _ = self.config.save();
+                }
+                Task::none()
+            }
+
+            // User typed in the custom resolution field; only save valid "WxH" sizes
+            Message::CustomResolutionChanged(size) => {
+                if crate::display::parse_mode(&size).is_some() {
+                    self.config.resolution = Resolution::Custom(size.trim().to_string());
+                    let _ = self.config.save();
+                }
+                self.custom_resolution = size;
+                Task::none()
+            }
+
+            // Applies to images downloaded from now on; existing files keep their names
+            Message::FilenameTemplateChanged(template) => {
+                self.config.filename_template = template;
+                let _ = self.config.save();
+                Task::none()
+            }
+
+            Message::SubdirectoriesSelected(idx) => {
+                if let Some(subdirectories) = Subdirectories::ALL.get(idx) {
+                    self.selected_subdirectories_idx = idx;
+                    self.config.subdirectories = *subdirectories;
+                    let _ = self.config.save();
+                }
+                Task::none()
+            }
+
+            // Post-processing: takes effect the next time a wallpaper is applied
+            Message::ProcessingToggled(enabled) => {
+                self.config.processing.enabled = enabled;
+                let _ = self.config.save();
+                Task::none()
+            }
+
+            Message::CropAspectChanged(aspect) => {
+                self.config.processing.crop_aspect = aspect;
+                let _ = self.config.save();
+                Task::none()
+            }
+
+            Message::BlurChanged(blur) => {
+                self.config.processing.blur = blur;
+                let _ = self.config.save();
+                Task::none()
+            }
+
+            Message::BrightnessChanged(brightness) => {
+                self.config.processing.brightness = brightness;
+                let _ = self.config.save();
+                Task::none()
+            }
+
+            Message::DesaturateChanged(desaturate) => {
+                self.config.processing.desaturate = desaturate;
+                let _ = self.config.save();
+                Task::none()
+            }
+
+            Message::VignetteChanged(vignette) => {
+                self.config.processing.vignette = vignette;
+                let _ = self.config.save();
+                Task::none()
+            }
+
+            // Caption overlay: also takes effect the next time a wallpaper is applied
+            Message::OverlayToggled(enabled) => {
+                self.config.overlay.enabled = enabled;
+                let _ = self.config.save();
+                Task::none()
+            }
+
+            Message::OverlayPositionSelected(idx) => {
+                if let Some(position) = OverlayPosition::ALL.get(idx) {
+                    self.selected_overlay_position_idx = idx;
+                    self.config.overlay.position = *position;
+                    let _ = self.config.save();
+                }
+                Task::none()
+            }
+
+            Message::FontSizeChanged(font_size) => {
+                self.config.overlay.font_size = font_size;
+                let _ = self.config.save();
+                Task::none()
+            }
+
+            Message::OverlayOpacityChanged(opacity) => {
+                self.config.overlay.opacity = opacity;
+                let _ = self.config.save();
+                Task::none()
+            }
+
+            Message::ScrimChanged(scrim) => {
+                self.config.overlay.scrim = scrim;
+                let _ = self.config.save();
+                Task::none()
+            }
+
+            // COSMIC background options: take effect the next time a wallpaper is applied
+            Message::ScalingSelected(idx) => {
+                if let Some(mode) = ScalingMode::ALL.get(idx) {
+                    self.selected_scaling_idx = idx;
+                    self.config.scaling_mode = *mode;
+                    let _ = self.config.save();
+                }
+                Task::none()
+            }
+
+            Message::FilterMethodSelected(idx) => {
+                if let Some(method) = FilterMethod::ALL.get(idx) {
+                    self.selected_filter_method_idx = idx;
+                    self.config.filter_method = *method;
+                    let _ = self.config.save();
+                }
+                Task::none()
+            }
+
+            // Per-display wallpapers: take effect the next time a wallpaper is applied
+            Message::PerOutputToggled(enabled) => {
+                self.config.outputs = if enabled {
+                    self.output_names
+                        .iter()
+                        .map(|name| OutputWallpaper { output: name.clone(), ..OutputWallpaper::default() })
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+                let _ = self.config.save();
+                Task::none()
+            }
+
+            Message::OutputDaySelected(output_idx, days_ago) => {
+                if let Some(output) = self.config.outputs.get_mut(output_idx) {
+                    output.days_ago = days_ago as u32;
                     let _ = self.config.save();
                 }
                 Task::none()
             }
 
+            Message::OutputMarketSelected(output_idx, idx) => {
+                let code = match idx {
+                    0 => Some(String::new()),
+                    _ => self.market_codes.get(idx - 1).cloned(),
+                };
+                if let Some(code) = code {
+                    if let Err(e) = self.config.validate_optional_market(&code) {
+                        self.status_message = e.status_message();
+                    } else if let Some(output) = self.config.outputs.get_mut(output_idx) {
+                        output.market = code;
+                        let _ = self.config.save();
+                    }
+                }
+                Task::none()
+            }
+
+            // Dark variant: the applet switches variants when the theme changes
+            Message::DarkVariantToggled(enabled) => {
+                self.config.dark_variant.enabled = enabled;
+                let _ = self.config.save();
+                Task::none()
+            }
+
+            Message::DarkDimChanged(dim) => {
+                self.config.dark_variant.dim = dim;
+                let _ = self.config.save();
+                Task::none()
+            }
+
             // --- View navigation ---
             Message::ShowHistory => {
                 self.view_mode = ViewMode::History;
@@ -415,6 +977,7 @@ impl Application for SettingsApp {
                     if let Err(e) = std::fs::remove_file(&path) {
                         self.status_message = format!("Failed to delete: {e}");
                     } else {
+                        metadata::forget(&path.to_string_lossy());
                         // Rescan to update the list after deletion
                         self.history = scan_history(&self.config.wallpaper_dir);
                         self.status_message = "Image deleted".to_string();
@@ -430,6 +993,14 @@ impl Application for SettingsApp {
                 Task::none()
             }
 
+            Message::OpenLink(url) => {
+                // xdg-open hands the URL to the default browser (works via portal in Flatpak)
+                if let Err(e) = std::process::Command::new("xdg-open").arg(&url).spawn() {
+                    self.status_message = format!("Failed to open link: {e}");
+                }
+                Task::none()
+            }
+
             // --- Timer management via D-Bus ---
 
             // Periodic timer status check (every 5 seconds)
@@ -519,6 +1090,7 @@ impl Application for SettingsApp {
                 // (avoids overwriting a freshly fetched image)
                 if let Some(p) = path {
                     if self.image_path.is_none() {
+                        self.current_image = metadata::lookup(&p);
                         self.image_path = Some(p);
                     }
                 }
@@ -554,10 +1126,34 @@ impl SettingsApp {
     /// │ │  Status: Ready                  │ │
     /// │ └────────────────────────────────┘ │
     /// │ ┌────────────────────────────────┐ │
-    /// │ │  Region: [United States ▼]      │ │
+    /// │ │  Source: [Bing ▼]               │ │
+    /// │ │  Region: [United States ▼]      │ │  (Bing only)
+    /// │ │  Caption Language: [Same ▼]     │ │  (Bing only)
+    /// │ │  Resolution: [Auto ▼]           │ │  (Bing only)
+    /// │ │  Filename / Subfolders          │ │  (Bing only)
+    /// │ │  Folder: [~/Pictures]           │ │  (Local Folder only)
+    /// │ │  Feed URL / selectors           │ │  (Custom Feed only)
     /// │ │  Daily Update:    (...) [toggle]│ │
     /// │ └────────────────────────────────┘ │
     /// │ ┌────────────────────────────────┐ │
+    /// │ │  Edit Before Applying [toggle]  │ │
+    /// │ │  Crop / Blur / Brightness / ... │ │  (when enabled)
+    /// │ └────────────────────────────────┘ │
+    /// │ ┌────────────────────────────────┐ │
+    /// │ │  Show Caption      [toggle]     │ │
+    /// │ │  Corner / Size / Opacity / ...  │ │  (when enabled)
+    /// │ └────────────────────────────────┘ │
+    /// │ ┌────────────────────────────────┐ │
+    /// │ │  Scaling: [As in COSMIC ▼]      │ │
+    /// │ │  Filter: [As in COSMIC ▼]       │ │
+    /// │ │  Different per Display [toggle] │ │
+    /// │ │  DP-1: [Same Day ▼] [Same ▼]    │ │  (per display, when enabled)
+    /// │ └────────────────────────────────┘ │
+    /// │ ┌────────────────────────────────┐ │
+    /// │ │  Darker in Dark Mode [toggle]   │ │
+    /// │ │  Dim By: ──●── 35%              │ │  (when enabled)
+    /// │ └────────────────────────────────┘ │
+    /// │ ┌────────────────────────────────┐ │
     /// │ │  [Fetch Today's Wallpaper]      │ │
     /// │ │  [History]                      │ │
     /// │ └────────────────────────────────┘ │
@@ -585,11 +1181,11 @@ impl SettingsApp {
 
         // Show image title and copyright, or em-dash (—) if no image has been fetched
         let image_title = self.current_image.as_ref()
-            .map(|img| img.title.clone())
+            .map(|img| img.display_title().to_string())
             .unwrap_or_else(|| "\u{2014}".to_string());   // \u{2014} = em-dash "—"
 
         let image_copyright = self.current_image.as_ref()
-            .map(|img| img.copyright.clone())
+            .map(|img| img.display_copyright().to_string())
             .unwrap_or_else(|| "\u{2014}".to_string());
 
         // --- Build the page layout using COSMIC settings widgets ---
@@ -626,6 +1222,17 @@ impl SettingsApp {
                 )
             );
 
+        // Link to Bing's page about the image, when the metadata has one
+        let wallpaper_section = match self.current_image.as_ref().map(|img| &img.copyright_link) {
+            Some(link) if !link.is_empty() => wallpaper_section.add(
+                settings::item(
+                    "Source",
+                    button::link("Learn More").on_press(Message::OpenLink(link.clone())),
+                )
+            ),
+            _ => wallpaper_section,
+        };
+
         // --- Timer status for the settings section ---
         // Determine toggle state and description text from the current timer status
         let timer_enabled = matches!(&self.timer_status, TimerStatus::Installed { .. });
@@ -636,15 +1243,152 @@ impl SettingsApp {
             TimerStatus::Error(e) => format!("Error: {e}"),
         };
 
-        let settings_section = settings::section()
+        let mut settings_section = settings::section()
             .title("Settings")
             .add(
                 settings::item(
-                    "Region",
-                    dropdown(&self.market_names, Some(self.selected_market_idx), Message::MarketSelected)
+                    "Source",
+                    dropdown(&self.source_names, Some(self.selected_source_idx), Message::SourceSelected)
                         .width(Length::Fixed(200.0)),
                 )
-            )
+            );
+
+        // Source-specific settings: Bing market and size, or the local folder
+        settings_section = match self.config.source {
+            SourceKind::Bing => {
+                let section = settings_section.add(
+                    settings::item(
+                        "Region Mode",
+                        dropdown(&self.market_mode_names, Some(self.selected_market_mode_idx), Message::MarketModeSelected)
+                            .width(Length::Fixed(200.0)),
+                    )
+                );
+                // One region, or the list to rotate through / pick from
+                let section = if self.config.market_mode == MarketMode::Fixed {
+                    section
+                        .add(
+                            settings::item(
+                                "Region",
+                                dropdown(&self.market_names, Some(self.selected_market_idx), Message::MarketSelected)
+                                    .width(Length::Fixed(200.0)),
+                            )
+                        )
+                        // Any other Bing market code, checked against the API before it's added
+                        .add(
+                            settings::item(
+                                "Custom Region",
+                                row()
+                                    .spacing(8)
+                                    .push(
+                                        text_input("e.g. es-CL", &self.custom_market)
+                                            .on_input(Message::CustomMarketChanged)
+                                            .width(Length::Fixed(120.0)),
+                                    )
+                                    .push(
+                                        button::standard("Add")
+                                            .on_press_maybe(
+                                                (!self.custom_market.trim().is_empty())
+                                                    .then_some(Message::AddCustomMarket),
+                                            ),
+                                    ),
+                            )
+                        )
+                } else {
+                    let placeholder = if self.config.market_mode == MarketMode::Random {
+                        "e.g. en-US, ja-JP (empty = all)"
+                    } else {
+                        "e.g. en-US, ja-JP, de-DE"
+                    };
+                    section.add(
+                        settings::item(
+                            format!("Regions (today: {})", self.config.todays_market()),
+                            text_input(placeholder, &self.market_list)
+                                .on_input(Message::MarketListChanged)
+                                .width(Length::Fixed(300.0)),
+                        )
+                    )
+                };
+                section
+                    .add(
+                        settings::item(
+                            "Caption Language",
+                            dropdown(&self.caption_names, Some(self.selected_caption_idx), Message::CaptionMarketSelected)
+                                .width(Length::Fixed(200.0)),
+                        )
+                    )
+                    .add(
+                        settings::item(
+                            "Resolution",
+                            dropdown(&self.resolution_names, Some(self.selected_resolution_idx), Message::ResolutionSelected)
+                                .width(Length::Fixed(200.0)),
+                        )
+                    )
+                    // How downloaded images are named (see naming.rs for the placeholders)
+                    .add(
+                        settings::item(
+                            "Filename",
+                            text_input(crate::naming::DEFAULT_TEMPLATE, &self.config.filename_template)
+                                .on_input(Message::FilenameTemplateChanged)
+                                .width(Length::Fixed(300.0)),
+                        )
+                    )
+                    .add(
+                        settings::item(
+                            "Subfolders",
+                            dropdown(&self.subdirectories_names, Some(self.selected_subdirectories_idx), Message::SubdirectoriesSelected)
+                                .width(Length::Fixed(200.0)),
+                        )
+                    )
+            }
+            SourceKind::LocalFolder => settings_section.add(
+                settings::item(
+                    "Folder",
+                    text_input("e.g. ~/Pictures/Wallpapers", &self.config.local_folder)
+                        .on_input(Message::LocalFolderChanged)
+                        .width(Length::Fixed(300.0)),
+                )
+            ),
+            SourceKind::Feed => settings_section
+                .add(
+                    settings::item(
+                        "Feed URL",
+                        text_input("https://example.com/feed.xml", &self.config.feed_url)
+                            .on_input(Message::FeedUrlChanged)
+                            .width(Length::Fixed(300.0)),
+                    )
+                )
+                // Selectors only matter for JSON feeds; RSS/Atom use enclosures
+                .add(
+                    settings::item(
+                        "Image Selector (JSON)",
+                        text_input("$.images[0].url", &self.config.feed_image_selector)
+                            .on_input(Message::FeedImageSelectorChanged)
+                            .width(Length::Fixed(300.0)),
+                    )
+                )
+                .add(
+                    settings::item(
+                        "Title Selector (JSON)",
+                        text_input("$.images[0].title", &self.config.feed_title_selector)
+                            .on_input(Message::FeedTitleSelectorChanged)
+                            .width(Length::Fixed(300.0)),
+                    )
+                ),
+        };
+
+        // Only show the size input when "Custom" is selected
+        if self.config.source == SourceKind::Bing && self.selected_resolution_idx == Resolution::PRESETS.len() {
+            settings_section = settings_section.add(
+                settings::item(
+                    "Custom Size",
+                    text_input("e.g. 1366x768", &self.custom_resolution)
+                        .on_input(Message::CustomResolutionChanged)
+                        .width(Length::Fixed(200.0)),
+                )
+            );
+        }
+
+        let settings_section = settings_section
             .add(
                 settings::flex_item(
                     "Daily Update",
@@ -665,6 +1409,170 @@ impl SettingsApp {
                 )
             );
 
+        // --- Post-processing ---
+        // Edits a copy of the wallpaper before it's applied (see process.rs);
+        // the steps are only shown while processing is on
+        let slider_row = |slider: Element<'static, Message>, value: String| {
+            row()
+                .spacing(12)
+                .align_y(cosmic::iced::Alignment::Center)
+                .push(container(slider).width(Length::Fixed(200.0)))
+                .push(text::caption(value))
+        };
+        let processing = &self.config.processing;
+        let mut processing_section = settings::section()
+            .title("Processing")
+            .add(
+                settings::item(
+                    "Edit Before Applying",
+                    toggler(processing.enabled).on_toggle(Message::ProcessingToggled),
+                )
+            );
+        if processing.enabled {
+            processing_section = processing_section
+                .add(
+                    settings::item(
+                        "Crop to Aspect Ratio",
+                        text_input("e.g. 21:9 or display", &processing.crop_aspect)
+                            .on_input(Message::CropAspectChanged)
+                            .width(Length::Fixed(200.0)),
+                    )
+                )
+                .add(
+                    settings::item(
+                        "Blur",
+                        slider_row(widget::slider(0..=20, processing.blur, Message::BlurChanged).into(), format!("{} px", processing.blur)),
+                    )
+                )
+                .add(
+                    settings::item(
+                        "Brightness",
+                        slider_row(widget::slider(-100..=100, processing.brightness, Message::BrightnessChanged).into(), format!("{:+}%", processing.brightness)),
+                    )
+                )
+                .add(
+                    settings::item(
+                        "Desaturate",
+                        slider_row(widget::slider(0..=100, processing.desaturate, Message::DesaturateChanged).into(), format!("{}%", processing.desaturate)),
+                    )
+                )
+                .add(
+                    settings::item(
+                        "Vignette",
+                        slider_row(widget::slider(0..=100, processing.vignette, Message::VignetteChanged).into(), format!("{}%", processing.vignette)),
+                    )
+                );
+        }
+
+        // --- Caption overlay ---
+        // Draws the title, date and copyright onto the wallpaper (see overlay.rs)
+        let overlay = &self.config.overlay;
+        let mut overlay_section = settings::section()
+            .title("Caption")
+            .add(
+                settings::item(
+                    "Show Caption on Wallpaper",
+                    toggler(overlay.enabled).on_toggle(Message::OverlayToggled),
+                )
+            );
+        if overlay.enabled {
+            overlay_section = overlay_section
+                .add(
+                    settings::item(
+                        "Corner",
+                        dropdown(&self.overlay_position_names, Some(self.selected_overlay_position_idx), Message::OverlayPositionSelected),
+                    )
+                )
+                .add(
+                    settings::item(
+                        "Font Size",
+                        slider_row(widget::slider(12..=72, overlay.font_size, Message::FontSizeChanged).into(), format!("{} px", overlay.font_size)),
+                    )
+                )
+                .add(
+                    settings::item(
+                        "Text Opacity",
+                        slider_row(widget::slider(10..=100, overlay.opacity, Message::OverlayOpacityChanged).into(), format!("{}%", overlay.opacity)),
+                    )
+                )
+                .add(
+                    settings::item(
+                        "Background",
+                        slider_row(widget::slider(0..=100, overlay.scrim, Message::ScrimChanged).into(), format!("{}%", overlay.scrim)),
+                    )
+                );
+        }
+
+        // --- Per-display wallpapers ---
+        // Each display can show another day or market than the main wallpaper (see outputs.rs).
+        // Scaling and filtering are cosmic-bg's own options (see background.rs)
+        let mut outputs_section = settings::section()
+            .title("Displays")
+            .add(
+                settings::item(
+                    "Scaling",
+                    dropdown(&self.scaling_names, Some(self.selected_scaling_idx), Message::ScalingSelected)
+                        .width(Length::Fixed(200.0)),
+                )
+            )
+            .add(
+                settings::item(
+                    "Filter",
+                    dropdown(&self.filter_method_names, Some(self.selected_filter_method_idx), Message::FilterMethodSelected)
+                        .width(Length::Fixed(200.0)),
+                )
+            )
+            .add(
+                settings::item(
+                    "Different Wallpaper per Display",
+                    toggler(!self.config.outputs.is_empty()).on_toggle(Message::PerOutputToggled),
+                )
+            );
+        if self.output_names.is_empty() && self.config.outputs.is_empty() {
+            outputs_section = outputs_section.add(text::caption("No displays found"));
+        }
+        for (output_idx, output) in self.config.outputs.iter().enumerate() {
+            let market_idx = self.market_codes
+                .iter()
+                .position(|code| *code == output.market)
+                .map_or(0, |idx| idx + 1);
+            outputs_section = outputs_section.add(
+                settings::item(
+                    output.output.clone(),
+                    row()
+                        .spacing(8)
+                        .push(
+                            dropdown(&self.days_ago_names, Some((output.days_ago as usize).min(self.days_ago_names.len() - 1)), move |days| Message::OutputDaySelected(output_idx, days))
+                                .width(Length::Fixed(140.0)),
+                        )
+                        .push(
+                            dropdown(&self.caption_names, Some(market_idx), move |idx| Message::OutputMarketSelected(output_idx, idx))
+                                .width(Length::Fixed(180.0)),
+                        ),
+                )
+            );
+        }
+
+        // --- Dark mode ---
+        // A darker copy is used while the COSMIC theme is dark (see theme.rs)
+        let dark_variant = &self.config.dark_variant;
+        let mut dark_section = settings::section()
+            .title("Dark Mode")
+            .add(
+                settings::item(
+                    "Darker Wallpaper in Dark Mode",
+                    toggler(dark_variant.enabled).on_toggle(Message::DarkVariantToggled),
+                )
+            );
+        if dark_variant.enabled {
+            dark_section = dark_section.add(
+                settings::item(
+                    "Dim By",
+                    slider_row(widget::slider(0..=80, dark_variant.dim, Message::DarkDimChanged).into(), format!("{}%", dark_variant.dim)),
+                )
+            );
+        }
+
         // --- Action buttons ---
         // on_press_maybe: passes None to disable the button while loading
         let fetch_btn = button::suggested("Fetch Today's Wallpaper")
@@ -688,6 +1596,10 @@ impl SettingsApp {
             page_title.into(),
             wallpaper_section.into(),
             settings_section.into(),
+            processing_section.into(),
+            overlay_section.into(),
+            outputs_section.into(),
+            dark_section.into(),
             actions_section.into(),
         ]);
 
@@ -750,11 +1662,23 @@ impl SettingsApp {
                     .width(Length::Fixed(160.0))
                     .height(Length::Fixed(90.0));
 
-                // Date and filename info
-                let info = column()
-                    .spacing(4)
-                    .push(text::body(item.date.clone()))
-                    .push(text::caption(item.filename.clone()));
+                // Title (when known from the metadata index), date and filename
+                let info = if item.title.is_empty() {
+                    column()
+                        .spacing(4)
+                        .push(text::body(item.date.clone()))
+                        .push(text::caption(item.filename.clone()))
+                } else {
+                    column()
+                        .spacing(4)
+                        .push(text::body(item.title.clone()))
+                        .push(text::caption(format!("{} \u{00B7} {}", item.date, item.copyright)))
+                };
+                let info = if item.copyright_link.is_empty() {
+                    info
+                } else {
+                    info.push(button::link("Learn More").on_press(Message::OpenLink(item.copyright_link.clone())))
+                };
 
                 // "Apply" button — sets this wallpaper as the current desktop background
                 let apply_btn = button::suggested("Apply")
@@ -818,45 +1742,28 @@ impl SettingsApp {
     }
 }
 
-/// Scans the wallpaper directory for downloaded image files and returns them as HistoryItems.
-///
-/// This reads the directory, filters for image files (.jpg, .jpeg, .png),
-/// extracts the date from each filename, and returns them sorted newest-first.
+/// Builds the caption language dropdown: "Same as region" followed by every
+/// market, and the index of `caption_market` in it (0 if empty or unknown).
+fn caption_choices(market_codes: &[String], market_names: &[String], caption_market: &str) -> (Vec<String>, usize) {
+    let mut names = vec!["Same as region".to_string()];
+    names.extend(market_names.iter().cloned());
+    let selected = market_codes
+        .iter()
+        .position(|code| code == caption_market)
+        .map_or(0, |idx| idx + 1);
+    (names, selected)
+}
+
+/// Lists the downloaded images and returns them as HistoryItems, newest
+/// first and each photo once (see [`crate::service::scan_history`]).
 ///
 /// # Arguments
 /// * `wallpaper_dir` - Path to the wallpaper storage directory (e.g., "~/Pictures/BingWallpapers")
 fn scan_history(wallpaper_dir: &str) -> Vec<HistoryItem> {
-    let dir = std::path::Path::new(wallpaper_dir);
-    if !dir.exists() {
-        return Vec::new();
-    }
-
-    let mut items: Vec<HistoryItem> = std::fs::read_dir(dir)
-        .ok()
-        .into_iter()       // Convert Option to Iterator (allows .flatten() to work)
-        .flatten()         // Unwrap the Ok variants from the directory entries
-        .filter_map(|entry| entry.ok())  // Skip any entries that failed to read
-        .filter(|entry| {
-            // Only include image files
-            entry.path().extension()
-                .map(|ext| ext == "jpg" || ext == "jpeg" || ext == "png")
-                .unwrap_or(false)
-        })
-        .map(|entry| {
-            let path = entry.path();
-            let filename = path.file_name()
-                .map(|s| s.to_string_lossy().to_string())
-                .unwrap_or_default();
-            // Extract date from filename like "bing-en-US-2026-02-05.jpg" → "2026-02-05"
-            let date = extract_date_from_filename(&filename);
-
-            HistoryItem { path, filename, date }
-        })
-        .collect();
-
-    // Sort by date, newest first (reverse alphabetical works for YYYY-MM-DD format)
-    items.sort_by(|a, b| b.date.cmp(&a.date));
-    items
+    crate::service::scan_history(wallpaper_dir)
+        .into_iter()
+        .map(HistoryItem::from)
+        .collect()
 }
 
 /// Checks the current timer status by querying the panel applet via D-Bus.
@@ -937,21 +1844,15 @@ async fn uninstall_timer() -> Result<(), String> {
 
 /// Applies a wallpaper to the COSMIC desktop.
 ///
-/// This is an async wrapper around the sync `service::apply_cosmic_wallpaper()` function.
-/// We use `spawn_blocking` to run the sync function on a thread pool thread,
-/// preventing it from blocking the async executor (which would freeze the UI).
-async fn apply_cosmic_wallpaper(image_path: &str) -> Result<(), String> {
-    let path = image_path.to_string();
-    tokio::task::spawn_blocking(move || {
-        crate::service::apply_cosmic_wallpaper(&path)
-    })
-    .await
-    .map_err(|e| format!("Task join error: {e}"))?
+/// `service::apply_cosmic_wallpaper()` does its work on a thread of its own,
+/// so awaiting it doesn't block the async executor (which would freeze the UI).
+async fn apply_cosmic_wallpaper(image_path: &str) -> Result<(), Error> {
+    crate::service::apply_cosmic_wallpaper(image_path).await
 }
 
 /// Public async wrapper for headless wallpaper application.
 /// Used by main.rs in --fetch mode (no GUI, just fetch and apply).
-pub async fn apply_wallpaper_headless(image_path: &str) -> Result<(), String> {
+pub async fn apply_wallpaper_headless(image_path: &str) -> Result<(), Error> {
     apply_cosmic_wallpaper(image_path).await
 }
 
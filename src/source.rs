@@ -0,0 +1,445 @@
+//! # Wallpaper Sources
+//!
+//! Abstracts where the daily image comes from, so the scheduler, history,
+//! metadata index and apply machinery work the same for every source.
+//!
+//! ## Sources
+//! - [`BingSource`](crate::bing::BingSource) - Bing's Image of the Day (default)
+//! - [`LocalFolderSource`](crate::local::LocalFolderSource) - Picks from a user-chosen folder
+//! - [`FeedSource`](crate::feed::FeedSource) - Follows an RSS, Atom or JSON feed
+//!
+//! The active source is selected by [`Config::source`] and built with
+//! [`from_config`].
+//!
+//! ## Fetch Pipeline
+//! Every source goes through the same two steps:
+//! 1. [`WallpaperSource::fetch_info`] returns metadata, including the filename
+//!    the image will be stored under in the wallpaper directory
+//! 2. [`download`] skips the fetch if that file (or a duplicate) already
+//!    exists, otherwise has the source write the image to a hidden partial
+//!    file via [`WallpaperSource::fetch_image`], validates it, renames it into
+//!    place and records it in the metadata index
+//!
+//! Because the final file only appears through an atomic rename, a crash can
+//! never leave a truncated image under the real name. An interrupted partial
+//! file (`.{filename}.part`) is resumed by the next attempt.
+//!
+//! ## Validation
+//! [`validate_image`] fully decodes every image before it is kept, rejecting
+//! error pages, truncated or corrupt files and images smaller than
+//! [`MIN_IMAGE_SIDE`]. Files that are already stored are validated again
+//! before being reused, so a broken file left by an older version is
+//! downloaded again instead of being treated as complete forever.
+
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+
+use crate::config::{Config, SourceKind};
+use crate::error::Error;
+use crate::http::{NetworkSettings, Progress};
+use crate::metadata::{self, ImageMetadata, MetadataIndex};
+use crate::network::NetworkStatus;
+
+/// Smallest accepted length of an image's short side, in pixels.
+///
+/// Bing's smallest variants (640x480, 480x800) just pass; thumbnails and
+/// placeholder images don't.
+pub const MIN_IMAGE_SIDE: u32 = 480;
+
+/// Boxed future returned by [`WallpaperSource`] methods.
+///
+/// Sources are chosen at runtime from the config, so the trait must be usable
+/// as `dyn WallpaperSource`, which rules out plain `async fn` in the trait.
+pub type SourceFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, Error>> + Send + 'a>>;
+
+/// A provider of daily wallpaper images.
+pub trait WallpaperSource: Send + Sync {
+    /// Fetches metadata for today's image.
+    ///
+    /// The returned metadata's `filename` is where the image will be stored
+    /// inside the wallpaper directory.
+    fn fetch_info(&self) -> SourceFuture<'_, ImageMetadata>;
+
+    /// Writes the image (JPEG or PNG) for metadata returned by
+    /// [`fetch_info`](WallpaperSource::fetch_info) to `dest`.
+    ///
+    /// `dest` may already hold the start of an interrupted earlier attempt,
+    /// which network sources resume. Byte progress is reported to `progress`.
+    fn fetch_image<'a>(
+        &'a self,
+        info: &'a ImageMetadata,
+        dest: &'a Path,
+        progress: &'a Progress,
+    ) -> SourceFuture<'a, ()>;
+}
+
+/// Builds the wallpaper source selected in the config.
+///
+/// Bing uses today's market from the market mode (fixed, rotating or random),
+/// plus the caption market, the configured filename template and the metered
+/// policy, applied to `network` (see [`crate::network::status_for`]). Bing and
+/// feeds make their requests with the configured proxy and certificates.
+pub fn from_config(config: &Config, network: NetworkStatus) -> Box<dyn WallpaperSource> {
+    match config.source {
+        SourceKind::Bing => Box::new(crate::bing::BingSource::new(
+            &config.bing_host,
+            &config.todays_market(),
+            config.resolution.clone(),
+        )
+        .with_caption_market(&config.caption_market)
+        .with_naming(crate::naming::FileNaming::from_config(config))
+        .with_network(config.metered, network)
+        .with_network_settings(NetworkSettings::from_config(config))),
+        SourceKind::LocalFolder => Box::new(crate::local::LocalFolderSource::new(&config.local_folder)),
+        SourceKind::Feed => Box::new(crate::feed::FeedSource::new(
+            &config.feed_url,
+            &config.feed_image_selector,
+            &config.feed_title_selector,
+        )
+        .with_network_settings(NetworkSettings::from_config(config))),
+    }
+}
+
+/// Returns the path of an already-stored, valid copy of an image, if any.
+///
+/// Checks the image's own filename first, then the metadata index for the
+/// same photo stored under another name (matched on Bing's `hsh`).
+pub fn existing_path(info: &ImageMetadata, wallpaper_dir: &str) -> Option<String> {
+    let filepath = Path::new(wallpaper_dir).join(&info.filename);
+    if read_valid_image(&filepath).is_ok() {
+        return Some(filepath.to_string_lossy().to_string());
+    }
+
+    valid_duplicate(&MetadataIndex::load(wallpaper_dir), wallpaper_dir, &info.hsh)
+}
+
+/// Stores a source's image in the wallpaper directory.
+///
+/// The image is saved under `info.filename`. If a valid file already exists,
+/// the download is skipped and the existing path is returned; an invalid one
+/// (e.g., truncated) is replaced.
+///
+/// Markets often share the same photo. If another copy is already stored
+/// (same Bing `hsh`, or same content hash when there is none), that file is
+/// reused instead of saving a second copy.
+///
+/// The image's full metadata is recorded in the directory's
+/// [metadata index](crate::metadata) alongside the file.
+///
+/// # Arguments
+/// * `progress` - Receives byte progress while the image is downloaded
+///
+/// # Returns
+/// * `Ok(String)` - Absolute path to the downloaded (or existing) image file
+/// * `Err(Error)` - Error if directory creation, download, or save fails
+pub async fn download(
+    source: &dyn WallpaperSource,
+    info: &ImageMetadata,
+    wallpaper_dir: &str,
+    progress: &Progress,
+) -> Result<String, Error> {
+    // Create the wallpaper directory (and the image's date subdirectory) if needed
+    let filepath = Path::new(wallpaper_dir).join(&info.filename);
+    let filepath_str = filepath.to_string_lossy().to_string();
+    if let Some(parent) = filepath.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| Error::Io(format!("Failed to create wallpaper directory: {e}")))?;
+    }
+
+    // Skip download if a valid copy already exists (idempotent operation), but
+    // make sure files downloaded by older versions get their metadata recorded,
+    // and that a newly chosen caption language reaches the existing entry
+    let index = MetadataIndex::load(wallpaper_dir);
+    if filepath.exists() {
+        match read_valid_image(&filepath) {
+            Ok((bytes, (width, height))) => {
+                let recorded = index.get(&info.filename);
+                let new_caption = !info.caption_title.is_empty()
+                    && recorded.is_some_and(|m| m.caption_market != info.caption_market);
+                if recorded.is_none_or(|m| m.width == 0) || new_caption {
+                    let mut metadata = recorded.cloned().unwrap_or_else(|| info.clone());
+                    metadata.content_hash = metadata::content_hash(&bytes);
+                    metadata.width = width;
+                    metadata.height = height;
+                    if !info.caption_title.is_empty() {
+                        metadata.caption_market = info.caption_market.clone();
+                        metadata.caption_title = info.caption_title.clone();
+                        metadata.caption_copyright = info.caption_copyright.clone();
+                    }
+                    record_metadata(wallpaper_dir, metadata);
+                }
+                return Ok(filepath_str);
+            }
+            Err(e) => {
+                // e.g., truncated by a crash in an older version: download it again
+                eprintln!("Replacing invalid {}: {e}", info.filename);
+                let _ = std::fs::remove_file(&filepath);
+            }
+        }
+    }
+
+    // Reuse another market's copy of the same photo (Bing's hash matches)
+    if let Some(existing) = valid_duplicate(&index, wallpaper_dir, &info.hsh) {
+        return Ok(existing);
+    }
+
+    // Download to a hidden partial file; it only gets its real name once complete
+    let partial = partial_path(&filepath);
+    source.fetch_image(info, &partial, progress).await?;
+
+    let bytes = std::fs::read(&partial)
+        .map_err(|e| Error::Io(format!("Failed to read downloaded image: {e}")))?;
+    let (width, height) = match validate_image(&bytes) {
+        Ok(size) => size,
+        Err(e) => {
+            // Not worth resuming: start from scratch next time
+            let _ = std::fs::remove_file(&partial);
+            return Err(e);
+        }
+    };
+
+    // Catch duplicates Bing's hash missed (or didn't provide) by comparing file contents
+    let content_hash = metadata::content_hash(&bytes);
+    if let Some(existing) = valid_duplicate(&index, wallpaper_dir, &content_hash) {
+        let _ = std::fs::remove_file(&partial);
+        return Ok(existing);
+    }
+
+    // Move into place (atomic within the same directory)
+    std::fs::rename(&partial, &filepath)
+        .map_err(|e| Error::Io(format!("Failed to save image: {e}")))?;
+
+    let mut metadata = info.clone();
+    metadata.content_hash = content_hash;
+    metadata.width = width;
+    metadata.height = height;
+    record_metadata(wallpaper_dir, metadata);
+
+    Ok(filepath_str)
+}
+
+/// Path of the partial file an image is downloaded to: `.{filename}.part`
+/// next to it (hidden, and without an image extension so history scans skip it).
+fn partial_path(filepath: &Path) -> std::path::PathBuf {
+    let filename = filepath.file_name().unwrap_or_default().to_string_lossy();
+    filepath.with_file_name(format!(".{filename}.part"))
+}
+
+/// Checks that bytes are a complete, decodable image of a usable size.
+///
+/// JPEG starts with FF D8 FF, PNG starts with 89 50 4E 47. The decoders
+/// happily fill in missing data, so the end markers (JPEG's end-of-image,
+/// PNG's `IEND` chunk) are checked explicitly before the whole image is decoded.
+///
+/// # Returns
+/// * `Ok((width, height))` - The image's dimensions in pixels
+/// * `Err(Error)` - Why the image was rejected
+pub fn validate_image(bytes: &[u8]) -> Result<(u32, u32), Error> {
+    if bytes.len() < 12 {
+        return Err(Error::InvalidResponse("Downloaded file is too small to be an image".to_string()));
+    }
+    let is_jpeg = bytes[0] == 0xFF && bytes[1] == 0xD8 && bytes[2] == 0xFF;
+    let is_png = bytes[0] == 0x89 && bytes[1] == 0x50 && bytes[2] == 0x4E && bytes[3] == 0x47;
+    if !is_jpeg && !is_png {
+        return Err(Error::InvalidResponse("Downloaded content is not a valid image (may be an error page)".to_string()));
+    }
+
+    let complete = if is_jpeg {
+        jpeg_is_complete(bytes)
+    } else {
+        bytes[bytes.len().saturating_sub(64)..].windows(4).any(|w| w == b"IEND")
+    };
+    if !complete {
+        return Err(Error::InvalidResponse("Image is truncated (download was cut short)".to_string()));
+    }
+
+    let decoded = image::load_from_memory(bytes)
+        .map_err(|e| Error::InvalidResponse(format!("Image is corrupt: {e}")))?;
+    let (width, height) = (decoded.width(), decoded.height());
+    if width.min(height) < MIN_IMAGE_SIDE {
+        return Err(Error::InvalidResponse(format!(
+            "Image is too small ({width}x{height}, needs at least {MIN_IMAGE_SIDE} pixels on each side)"
+        )));
+    }
+
+    Ok((width, height))
+}
+
+/// Whether a JPEG's main image runs all the way to its end-of-image marker.
+///
+/// Walks the marker segments (skipping their payloads, so an EXIF thumbnail's
+/// own end marker doesn't count) and the entropy-coded scan data. Data after
+/// the end marker, like a phone's embedded motion video, is allowed.
+fn jpeg_is_complete(bytes: &[u8]) -> bool {
+    let is_restart = |marker: u8| (0xD0..=0xD7).contains(&marker);
+    let mut i = 2; // Skip the start-of-image marker
+
+    while i + 1 < bytes.len() {
+        if bytes[i] != 0xFF {
+            return false;
+        }
+        let marker = bytes[i + 1];
+        match marker {
+            0xD9 => return true,
+            0xFF => {
+                // Fill byte before a marker
+                i += 1;
+                continue;
+            }
+            0x01 => {
+                i += 2;
+                continue;
+            }
+            m if is_restart(m) => {
+                i += 2;
+                continue;
+            }
+            _ => {}
+        }
+
+        // Every other marker starts a segment with a two-byte length
+        let Some(len) = bytes.get(i + 2..i + 4) else {
+            return false;
+        };
+        i += 2 + u16::from_be_bytes([len[0], len[1]]) as usize;
+
+        // After a start-of-scan header comes entropy-coded data, which runs until
+        // the next marker that isn't byte stuffing (FF 00) or a restart marker
+        if marker == 0xDA {
+            while i + 1 < bytes.len()
+                && !(bytes[i] == 0xFF && bytes[i + 1] != 0x00 && !is_restart(bytes[i + 1]))
+            {
+                i += 1;
+            }
+        }
+    }
+    false
+}
+
+/// Reads and validates a stored image, returning its bytes and dimensions.
+fn read_valid_image(path: &Path) -> Result<(Vec<u8>, (u32, u32)), Error> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| Error::Io(format!("Failed to read {}: {e}", path.display())))?;
+    let size = validate_image(&bytes)?;
+    Ok((bytes, size))
+}
+
+/// Returns the path of a valid stored image with the given Bing hash or
+/// content hash, if any.
+fn valid_duplicate(index: &MetadataIndex, wallpaper_dir: &str, hash: &str) -> Option<String> {
+    let existing = index.find_duplicate(wallpaper_dir, hash)?;
+    let path = Path::new(wallpaper_dir).join(&existing.filename);
+    read_valid_image(&path)
+        .is_ok()
+        .then(|| path.to_string_lossy().to_string())
+}
+
+/// Records an image in the metadata index. A failure here only costs us the
+/// extra history details, so it is logged rather than failing the download.
+fn record_metadata(wallpaper_dir: &str, metadata: ImageMetadata) {
+    if let Err(e) = metadata::record(wallpaper_dir, metadata) {
+        eprintln!("Warning: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Encodes a blank image of the given size.
+    fn encode(width: u32, height: u32, format: image::ImageFormat) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image::DynamicImage::new_rgb8(width, height)
+            .write_to(&mut Cursor::new(&mut bytes), format)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_validate_image() {
+        let jpeg = encode(640, 480, image::ImageFormat::Jpeg);
+        assert_eq!(validate_image(&jpeg), Ok((640, 480)));
+        assert!(validate_image(&jpeg[..jpeg.len() / 2]).unwrap_err().message().contains("truncated"));
+        assert!(validate_image(&jpeg[..jpeg.len() - 2]).unwrap_err().message().contains("truncated"));
+
+        // Trailing data after the end marker (e.g., a motion photo's video) is fine
+        let mut with_trailer = jpeg.clone();
+        with_trailer.extend_from_slice(&[0x42; 500]);
+        assert_eq!(validate_image(&with_trailer), Ok((640, 480)));
+
+        let png = encode(480, 800, image::ImageFormat::Png);
+        assert_eq!(validate_image(&png), Ok((480, 800)));
+        assert!(validate_image(&png[..png.len() - 20]).is_err());
+
+        let thumbnail = encode(200, 150, image::ImageFormat::Png);
+        assert!(validate_image(&thumbnail).unwrap_err().message().contains("too small"));
+        assert!(validate_image(b"<html><body>Not found</body></html>").is_err());
+    }
+
+    /// A source that serves one fixed image.
+    struct FixedSource(Vec<u8>);
+
+    impl WallpaperSource for FixedSource {
+        fn fetch_info(&self) -> SourceFuture<'_, ImageMetadata> {
+            Box::pin(async { Ok(ImageMetadata::default()) })
+        }
+
+        fn fetch_image<'a>(
+            &'a self,
+            _info: &'a ImageMetadata,
+            dest: &'a Path,
+            _progress: &'a Progress,
+        ) -> SourceFuture<'a, ()> {
+            Box::pin(async move { std::fs::write(dest, &self.0).map_err(|e| Error::Io(e.to_string())) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_download_replaces_truncated_file() {
+        let dir = std::env::temp_dir().join(format!("bing-wallpaper-source-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let wallpaper_dir = dir.to_str().unwrap();
+
+        let jpeg = encode(640, 480, image::ImageFormat::Jpeg);
+        let info = ImageMetadata { filename: "bing-en-US-2026-02-05.jpg".to_string(), ..Default::default() };
+        std::fs::write(dir.join(&info.filename), &jpeg[..jpeg.len() / 2]).unwrap();
+        assert_eq!(existing_path(&info, wallpaper_dir), None);
+
+        let path = download(&FixedSource(jpeg.clone()), &info, wallpaper_dir, &crate::http::no_progress)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), jpeg);
+        let recorded = metadata::lookup(&path).unwrap();
+        assert_eq!((recorded.width, recorded.height), (640, 480));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_download_into_date_subdirectory() {
+        let dir = std::env::temp_dir().join(format!("bing-wallpaper-subdir-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let wallpaper_dir = dir.to_str().unwrap();
+
+        let jpeg = encode(640, 480, image::ImageFormat::Jpeg);
+        let info = ImageMetadata { filename: "2026/02/Fox.jpg".to_string(), title: "Fox".to_string(), ..Default::default() };
+        let path = download(&FixedSource(jpeg), &info, wallpaper_dir, &crate::http::no_progress)
+            .await
+            .unwrap();
+        assert_eq!(path, dir.join("2026/02/Fox.jpg").to_string_lossy());
+
+        // Found through the index one level up, by its relative path
+        assert_eq!(metadata::lookup(&path).unwrap().title, "Fox");
+        let stored = metadata::stored_images(wallpaper_dir);
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].0, dir.join("2026/02/Fox.jpg"));
+
+        metadata::forget(&path);
+        assert!(metadata::lookup(&path).is_none());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
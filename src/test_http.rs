@@ -0,0 +1,53 @@
+//! # Local HTTP Stand-In
+//!
+//! A tiny HTTP server for tests, so network code (Bing API, feeds, image
+//! downloads) can be exercised offline against canned responses.
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// Serves a fixed set of `(path, content type, body)` routes over plain HTTP
+/// on localhost until the test's runtime shuts down. Unknown paths get a 404.
+///
+/// Paths are matched including the query string, and `Range: bytes=N-`
+/// requests get a 206 with the rest of the body. Every body is sent with an
+/// `ETag` (a hash of its contents); a range request whose `If-Range` doesn't
+/// match it gets the whole body instead. Returns the base URL
+/// (e.g., "http://127.0.0.1:40123").
+pub async fn serve(routes: Vec<(&'static str, &'static str, Vec<u8>)>) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut buf = vec![0; 4096];
+            let n = stream.read(&mut buf).await.unwrap_or(0);
+            let request = String::from_utf8_lossy(&buf[..n]);
+            let path = request.split_whitespace().nth(1).unwrap_or("/");
+            let header = |name: &str| request.lines().find_map(|line| {
+                let (key, value) = line.split_once(':')?;
+                key.eq_ignore_ascii_case(name).then(|| value.trim().to_string())
+            });
+            let range_start = header("range")
+                .and_then(|range| range.strip_prefix("bytes=")?.trim_end_matches('-').parse::<usize>().ok());
+            let response = match routes.iter().find(|(p, _, _)| *p == path) {
+                Some((_, content_type, body)) => {
+                    let etag = format!("\"{}\"", &crate::metadata::content_hash(body)[..16]);
+                    let range_start = range_start.filter(|_| header("if-range").is_none_or(|v| v == etag));
+                    let (status, part) = match range_start {
+                        Some(start) if start < body.len() => ("206 Partial Content", &body[start..]),
+                        Some(_) => ("416 Range Not Satisfiable", &body[..0]),
+                        None => ("200 OK", &body[..]),
+                    };
+                    let mut r = format!(
+                        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nETag: {etag}\r\nConnection: close\r\n\r\n",
+                        part.len()
+                    ).into_bytes();
+                    r.extend_from_slice(part);
+                    r
+                }
+                None => b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec(),
+            };
+            let _ = stream.write_all(&response).await;
+        }
+    });
+    base
+}
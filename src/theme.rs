@@ -0,0 +1,163 @@
+//! # Theme Mode
+//!
+//! Follows whether the COSMIC theme is light or dark, for the darker
+//! wallpaper variant (see [`Config::dark_variant`](crate::config::Config)).
+//!
+//! COSMIC keeps the mode in its config directory, as a single RON value per
+//! key: `~/.config/cosmic/com.system76.CosmicTheme.Mode/v1/is_dark` holds
+//! `true` or `false`, and is rewritten whenever the user (or the automatic
+//! day/night switch) changes the theme. A missing or unreadable file counts
+//! as dark, COSMIC's default.
+//!
+//! COSMIC's own `filter_by_theme` background option only picks light or dark
+//! images out of a slideshow folder, so it can't help with a single daily
+//! photo; instead, the current wallpaper is applied again when the mode
+//! changes, and [`crate::process`] darkens the copy while the theme is dark.
+//! Both variants are cached, so switching back and forth never re-downloads
+//! or re-processes anything.
+//!
+//! ## Watching
+//! [`ThemeMonitor`] follows the `is_dark` key through cosmic-config's
+//! watcher, so a switch is noticed as soon as COSMIC writes it. Inside the
+//! Flatpak sandbox cosmic-config would watch the sandbox's config directory
+//! instead of the host's, so there (and whenever cosmic-config fails) the
+//! file is checked every couple of seconds instead.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use cosmic::cosmic_config::{self, ConfigGet};
+use tokio::sync::watch;
+
+/// COSMIC's theme mode config name and version, as used by cosmic-config.
+const MODE_CONFIG: &str = "com.system76.CosmicTheme.Mode";
+const MODE_VERSION: u64 = 1;
+
+/// The key holding whether the theme is dark.
+const IS_DARK: &str = "is_dark";
+
+/// How often [`ThemeMonitor`] checks the mode file when cosmic-config can't
+/// be watched.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Where COSMIC stores whether the theme is dark.
+///
+/// Like the background config, this uses home_dir() rather than config_dir(),
+/// which points into the sandbox under Flatpak.
+fn mode_path() -> Option<PathBuf> {
+    Some(dirs::home_dir()?.join(format!(".config/cosmic/{MODE_CONFIG}/v{MODE_VERSION}/{IS_DARK}")))
+}
+
+/// Whether the COSMIC theme is currently dark.
+pub fn is_dark() -> bool {
+    mode_path().map(|path| read_mode(&path)).unwrap_or(true)
+}
+
+/// Reads a mode file; anything but `false` (including no file) counts as dark.
+fn read_mode(path: &Path) -> bool {
+    std::fs::read_to_string(path).map(|value| value.trim() != "false").unwrap_or(true)
+}
+
+/// Follows the COSMIC theme mode.
+#[derive(Debug)]
+pub struct ThemeMonitor {
+    dark: watch::Receiver<bool>,
+}
+
+impl ThemeMonitor {
+    /// Starts watching COSMIC's theme mode. Must be called within a tokio runtime.
+    pub fn start() -> Self {
+        if !crate::service::is_flatpak() {
+            match Self::watch_config() {
+                Ok(monitor) => return monitor,
+                Err(e) => eprintln!("Failed to watch COSMIC's theme mode ({e}), checking it periodically"),
+            }
+        }
+        match mode_path() {
+            Some(path) => Self::poll(path, POLL_INTERVAL),
+            None => {
+                // Without a home directory nothing can change
+                let (_tx, rx) = watch::channel(true);
+                Self { dark: rx }
+            }
+        }
+    }
+
+    /// Follows the mode through cosmic-config, which reports each write to
+    /// the key.
+    fn watch_config() -> Result<Self, cosmic_config::Error> {
+        let config = cosmic_config::Config::new(MODE_CONFIG, MODE_VERSION)?;
+        let (tx, rx) = watch::channel(config.get::<bool>(IS_DARK).unwrap_or(true));
+        let tx = Arc::new(tx);
+        let watcher = {
+            let tx = tx.clone();
+            config.watch(move |config, keys| {
+                if keys.iter().any(|key| key == IS_DARK) {
+                    let now = config.get::<bool>(IS_DARK).unwrap_or(true);
+                    tx.send_if_modified(|dark| std::mem::replace(dark, now) != now);
+                }
+            })?
+        };
+        tokio::spawn(async move {
+            // The watcher stops when dropped: keep it until every receiver is gone
+            let _watcher = watcher;
+            tx.closed().await;
+        });
+        Ok(Self { dark: rx })
+    }
+
+    /// Follows a mode file at `path`, checking it every `interval`.
+    fn poll(path: PathBuf, interval: Duration) -> Self {
+        let (tx, rx) = watch::channel(read_mode(&path));
+        tokio::spawn(async move {
+            // Stops once every receiver is gone
+            while !tx.is_closed() {
+                tokio::time::sleep(interval).await;
+                tx.send_if_modified(|dark| {
+                    let now = read_mode(&path);
+                    std::mem::replace(dark, now) != now
+                });
+            }
+        });
+        Self { dark: rx }
+    }
+
+    /// Whether the theme is dark right now.
+    pub fn is_dark(&self) -> bool {
+        *self.dark.borrow()
+    }
+
+    /// Waits for the theme to switch and returns whether it is now dark, or
+    /// None if the watcher stopped.
+    pub async fn changed(&mut self) -> Option<bool> {
+        self.dark.changed().await.ok()?;
+        Some(*self.dark.borrow_and_update())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_follows_mode_file() {
+        let dir = std::env::temp_dir().join(format!("bing-wallpaper-theme-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("is_dark");
+
+        // No file yet: COSMIC's default
+        assert!(read_mode(&path));
+
+        std::fs::write(&path, "false").unwrap();
+        let mut monitor = ThemeMonitor::poll(path.clone(), Duration::from_millis(10));
+        assert!(!monitor.is_dark());
+
+        std::fs::write(&path, "true\n").unwrap();
+        let dark = tokio::time::timeout(Duration::from_secs(5), monitor.changed()).await.unwrap();
+        assert_eq!(dark, Some(true));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
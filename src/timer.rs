@@ -8,6 +8,10 @@
 //! - Catches up on missed runs after boot (with 5-minute initial delay)
 //! - Random delay up to 5 minutes to avoid API hammering
 //! - Persists enabled state and last run time to config
+//! - When a run comes due while the machine is offline (per NetworkManager),
+//!   the fetch is deferred and fires as soon as full connectivity returns
+//! - On a metered connection, the `metered` policy in the config can skip the
+//!   run or defer it until the connection is unmetered
 
 use chrono::{DateTime, Duration, Local, NaiveTime};
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -15,7 +19,8 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 use tokio::task::JoinHandle;
 
-use crate::config::app_config_dir;
+use crate::config::{app_config_dir, Config, MeteredPolicy};
+use crate::network::{NetworkMonitor, NetworkStatus};
 
 /// Default scheduled run time (08:00 local time)
 const SCHEDULED_HOUR: u32 = 8;
@@ -92,6 +97,8 @@ pub struct InternalTimer {
     enabled: Arc<AtomicBool>,
     /// Next scheduled run time
     next_run: Arc<RwLock<Option<DateTime<Local>>>>,
+    /// Why the last due run is being held back or was skipped (shown in the UI)
+    deferred: Arc<std::sync::Mutex<Option<String>>>,
     /// Background task handle (not cloneable, so wrapped in Option)
     handle: std::sync::Mutex<Option<JoinHandle<()>>>,
 }
@@ -101,6 +108,7 @@ impl Clone for InternalTimer {
         Self {
             enabled: self.enabled.clone(),
             next_run: self.next_run.clone(),
+            deferred: self.deferred.clone(),
             handle: std::sync::Mutex::new(None), // Handle is not cloned
         }
     }
@@ -113,6 +121,7 @@ impl InternalTimer {
         Self {
             enabled: Arc::new(AtomicBool::new(state.enabled)),
             next_run: Arc::new(RwLock::new(None)),
+            deferred: Arc::new(std::sync::Mutex::new(None)),
             handle: std::sync::Mutex::new(None),
         }
     }
@@ -120,11 +129,15 @@ impl InternalTimer {
     /// Start the timer with a callback channel
     ///
     /// When the timer fires, a message is sent on the returned receiver.
-    pub fn start(&self) -> tokio::sync::mpsc::Receiver<()> {
+    /// Runs that come due while `network` reports offline are held back
+    /// until it is online again; on a metered connection the metered policy
+    /// decides (see [`wait_for_network`]).
+    pub fn start(&self, mut network: NetworkMonitor) -> tokio::sync::mpsc::Receiver<()> {
         let (tx, rx) = tokio::sync::mpsc::channel(1);
 
         let enabled = self.enabled.clone();
         let next_run = self.next_run.clone();
+        let deferred = self.deferred.clone();
 
         let handle = tokio::spawn(async move {
             // Initial boot delay
@@ -139,8 +152,8 @@ impl InternalTimer {
                 let random_delay = rand_delay();
                 tokio::time::sleep(std::time::Duration::from_secs(random_delay)).await;
 
-                // Fire the callback for catch-up
-                if enabled.load(Ordering::SeqCst) {
+                // Fire the callback for catch-up (once the network is up)
+                if wait_for_network(&mut network, Config::load().metered, &enabled, &deferred).await {
                     let _ = tx.send(()).await;
                 }
             }
@@ -172,8 +185,8 @@ impl InternalTimer {
                     let random_delay = rand_delay();
                     tokio::time::sleep(std::time::Duration::from_secs(random_delay)).await;
 
-                    // Fire the callback
-                    if enabled.load(Ordering::SeqCst) {
+                    // Fire the callback (once the network is up)
+                    if wait_for_network(&mut network, Config::load().metered, &enabled, &deferred).await {
                         let _ = tx.send(()).await;
                     }
 
@@ -225,6 +238,9 @@ impl InternalTimer {
         if !self.is_enabled() {
             return String::new();
         }
+        if let Some(reason) = self.deferred_reason() {
+            return reason;
+        }
 
         match self.next_run().await {
             Some(dt) => dt.format("%a %b %d %H:%M").to_string(),
@@ -232,8 +248,17 @@ impl InternalTimer {
         }
     }
 
+    /// Why the last due run is being held back or was skipped, if it was
+    /// (e.g., "Waiting for network (offline)")
+    pub fn deferred_reason(&self) -> Option<String> {
+        self.deferred.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
     /// Record a successful fetch
     pub fn record_fetch(&self) {
+        // A fetch went through, so there's nothing left to explain
+        set_deferred(&self.deferred, None);
+
         let mut state = TimerState::load();
         state.set_last_fetch(Local::now());
         let _ = state.save();
@@ -295,6 +320,81 @@ fn check_needs_catchup(state: &TimerState) -> bool {
     true
 }
 
+/// Decides whether a due run may fire, waiting if the network isn't ready.
+///
+/// - Offline: waits until NetworkManager reports connectivity again
+/// - Metered, policy `skip`: drops the run (also when the connection that
+///   came back after waiting is metered, e.g. phone tethering after resume)
+/// - Metered, policy `defer`: waits until the connection is unmetered
+/// - Otherwise (including policy `reduce`, handled at download time): fires now
+///
+/// `policy` should be read from the config just before the call, since it
+/// may have changed in the settings window.
+///
+/// Returns false if the run is dropped, either by the policy or because the
+/// timer was disabled while waiting. `deferred` holds the reason meanwhile,
+/// so the UI can say why nothing happened yet.
+async fn wait_for_network(
+    network: &mut NetworkMonitor,
+    policy: MeteredPolicy,
+    enabled: &AtomicBool,
+    deferred: &std::sync::Mutex<Option<String>>,
+) -> bool {
+    if !enabled.load(Ordering::SeqCst) {
+        return false;
+    }
+
+    let ready = |status: &NetworkStatus| {
+        status.connectivity.is_online() && !(status.metered && policy == MeteredPolicy::Defer)
+    };
+    let skip = |status: &NetworkStatus| {
+        if status.metered && policy == MeteredPolicy::Skip {
+            eprintln!("Timer: metered connection, skipping this update");
+            set_deferred(deferred, Some("Skipped update: metered connection".to_string()));
+            true
+        } else {
+            false
+        }
+    };
+
+    let status = network.status();
+    if status.connectivity.is_online() && skip(&status) {
+        return false;
+    }
+    if ready(&status) {
+        set_deferred(deferred, None);
+        return true;
+    }
+
+    let reason = if status.connectivity.is_online() {
+        "Waiting for an unmetered connection".to_string()
+    } else {
+        format!("Waiting for network ({})", status.connectivity.label())
+    };
+    eprintln!("Timer: {reason}");
+    set_deferred(deferred, Some(reason));
+
+    // Check every minute whether the timer was disabled meanwhile
+    while enabled.load(Ordering::SeqCst) {
+        let wait = network.wait_until(ready);
+        if tokio::time::timeout(std::time::Duration::from_secs(60), wait).await.is_ok() {
+            // The connection that came back may be metered
+            if skip(&network.status()) {
+                return false;
+            }
+            set_deferred(deferred, None);
+            return true;
+        }
+    }
+    set_deferred(deferred, None);
+    false
+}
+
+/// Replaces the deferred-run reason shown in the UI.
+fn set_deferred(deferred: &std::sync::Mutex<Option<String>>, reason: Option<String>) {
+    *deferred.lock().unwrap_or_else(|e| e.into_inner()) = reason;
+}
+
 /// Generate a random delay (0 to MAX_RANDOM_DELAY_SECS)
 fn rand_delay() -> u64 {
     use std::time::{SystemTime, UNIX_EPOCH};
@@ -328,4 +428,28 @@ mod tests {
         assert!(!state.enabled);
         assert!(state.last_fetch.is_none());
     }
+
+    #[tokio::test]
+    async fn test_skips_run_when_network_returns_metered() {
+        use crate::network::tests::{mock_network_manager, MockNetworkManager, PATH};
+
+        let (server, client) = mock_network_manager(1).await;
+        let mut network = NetworkMonitor::watch(&client).await.unwrap();
+        let waiting = tokio::spawn(async move {
+            let (enabled, deferred) = (AtomicBool::new(true), std::sync::Mutex::new(None));
+            let fired = wait_for_network(&mut network, MeteredPolicy::Skip, &enabled, &deferred).await;
+            (fired, deferred.into_inner().unwrap())
+        });
+
+        // Resumed while offline, then back online through a phone hotspot
+        let iface = server.object_server().interface::<_, MockNetworkManager>(PATH).await.unwrap();
+        iface.get_mut().await.metered = 3;
+        iface.get().await.metered_changed(iface.signal_context()).await.unwrap();
+        iface.get_mut().await.connectivity = 4;
+        iface.get().await.connectivity_changed(iface.signal_context()).await.unwrap();
+
+        let (fired, reason) = tokio::time::timeout(std::time::Duration::from_secs(5), waiting).await.unwrap().unwrap();
+        assert!(!fired);
+        assert_eq!(reason.as_deref(), Some("Skipped update: metered connection"));
+    }
 }